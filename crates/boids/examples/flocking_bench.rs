@@ -0,0 +1,59 @@
+// Times the spatial grid against checking every pair, the flocking tests check both agree.
+// Run with: cargo run -p boids --release --example flocking_bench -- [num_boids]
+use bevy::prelude::*;
+use boids::flocking::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{hint::black_box, time::Instant};
+
+fn main() {
+    let num_boids = std::env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(10_000);
+
+    // Same values as BoidConfig's defaults
+    let params = FlockingParams {
+        max_velocity: 16.,
+        max_acceleration: 6.,
+        neighbor_radius: 20.,
+        field_of_vision: (2. / 3.) * std::f32::consts::TAU,
+        avoid_radius: 5.,
+        interflock_separation_force: 15.,
+        separation_force: 10.,
+        align_force: 1.,
+        cohesion_force: 1.,
+        target_force: 0.001,
+    };
+
+    // Spread out so each boid sees a handful of neighbors, like a running flock
+    let mut rng = StdRng::seed_from_u64(0);
+    let half_size = (num_boids as f32).sqrt() * 5.0;
+    let agents = (0..num_boids)
+        .map(|i| {
+            let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+            Agent {
+                position: Vec2::new(
+                    rng.gen_range(-half_size..half_size),
+                    rng.gen_range(-half_size..half_size),
+                ),
+                velocity: Vec2::new(angle.cos(), angle.sin()) * 4.0,
+                flock: i % 9,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut grid = SpatialGrid::default();
+    grid.rebuild(params.neighbor_radius, &agents);
+    black_box(grid_forces(&agents, &grid, &params));
+    let grid_time = start.elapsed();
+
+    let start = Instant::now();
+    black_box(brute_force_forces(&agents, &params));
+    let brute_time = start.elapsed();
+
+    println!(
+        "{} boids: grid {:.2?} per frame, brute force {:.2?} per frame",
+        num_boids, grid_time, brute_time
+    );
+}
@@ -0,0 +1,27 @@
+// Runs the simulation headless and prints the flock metrics as CSV.
+// Run with: cargo run -p boids --release --example stats -- [seed] [num_boids] [seconds] >
+// stats.csv
+use boids::{headless, stats::BoidStats, TIME_STEP};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let seed = args.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+    let num_boids = args.next().and_then(|n| n.parse().ok()).unwrap_or(300);
+    let seconds: f32 = args.next().and_then(|n| n.parse().ok()).unwrap_or(60.);
+
+    let steps = (seconds / TIME_STEP) as usize;
+    let world = headless::simulate(seed, num_boids, steps);
+    let stats = world.get_resource::<BoidStats>().unwrap();
+    print!("{}", stats.to_csv());
+
+    if let Some(latest) = stats.latest() {
+        eprintln!(
+            "after {:.1}s: speed {:.2}, polarization {:.3}, {} clusters, nearest neighbor {:.2}",
+            latest.time,
+            latest.average_speed,
+            latest.polarization,
+            latest.clusters,
+            latest.mean_nearest_neighbor
+        );
+    }
+}
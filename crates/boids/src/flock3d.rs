@@ -0,0 +1,371 @@
+// 3D flocking inside a box, using the same steering as the 2D mode
+use bevy::{prelude::*, tasks::ComputeTaskPool, utils::HashMap};
+use engine::prelude::*;
+use rand::Rng;
+
+use super::{flocking::*, BoidAssets, BoidConfig, BoidRng, Flock, TIME_STEP};
+
+/// Directions tried, closest to the heading first, when the way ahead is blocked
+const AVOIDANCE_RAYS: usize = 100;
+
+#[derive(Component, Default)]
+pub struct Boid3d {
+    pub force: Vec3, // Sum of the forces
+}
+
+#[derive(Component, Default)]
+pub struct Velocity3d(pub Vec3);
+
+/// Flocking, steering and movement for one `TIME_STEP` in 3D
+pub fn simulation_stage_3d() -> SystemStage {
+    SystemStage::parallel()
+        .with_system(flocking_3d.label("flocking"))
+        .with_system(heading_3d.after("flocking"))
+}
+
+pub(super) fn setup_3d(
+    mut commands: Commands,
+    config: Res<BoidConfig>,
+    assets: Res<BoidAssets>,
+    mut rng: ResMut<BoidRng>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let distance = config.bounds.max_element() * 2.5;
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(0.0, distance * 0.4, distance)
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..Default::default()
+        })
+        .insert(CameraController {
+            radius: distance,
+            target_radius: distance,
+            ..CameraController::orbit(Vec3::ZERO)
+        })
+        .insert(Name::new("Camera"));
+
+    commands
+        .spawn_bundle(DirectionalLightBundle {
+            transform: Transform::from_xyz(1.0, 2.0, 1.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..Default::default()
+        })
+        .insert(Name::new("DirectionalLight"));
+
+    // ShapePlugin builds the meshes, edit them in the inspector and the boids follow
+    let material = materials.add(StandardMaterial {
+        base_color: Color::GRAY,
+        ..Default::default()
+    });
+    let bounds = config.bounds;
+    let obstacles = [
+        (
+            "Obstacle - Sphere",
+            Vec3::new(bounds.x * 0.4, 0.0, 0.0),
+            Shape::Icosphere(Icosphere {
+                radius: bounds.min_element() * 0.25,
+                subdivisions: 3,
+            }),
+        ),
+        (
+            "Obstacle - Pillar",
+            Vec3::new(-bounds.x * 0.4, 0.0, -bounds.z * 0.3),
+            Shape::Capsule(Capsule {
+                radius: bounds.min_element() * 0.1,
+                depth: bounds.y * 1.2,
+                ..Default::default()
+            }),
+        ),
+        (
+            "Obstacle - Floor",
+            Vec3::new(0.0, -bounds.y * 0.9, 0.0),
+            Shape::Plane(Plane {
+                size: bounds.x.min(bounds.z) * 1.5,
+            }),
+        ),
+    ];
+    for (name, position, shape) in obstacles {
+        commands
+            .spawn_bundle(PbrBundle {
+                material: material.clone(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            })
+            .insert(ShapeInstance { value: shape })
+            .insert(Name::new(name));
+    }
+
+    // Rays are tested in each shape's local space, the obstacles only start out moved
+    let obstacles = obstacles
+        .iter()
+        .map(|(_, position, shape)| (Mat4::from_translation(-*position), *shape))
+        .collect::<Vec<_>>();
+
+    let num_flocks = assets.materials.len().max(1);
+    for i in 0..config.num_boids {
+        let (transform, boid, velocity, flock) =
+            boid_state_3d(&mut rng, &config, &obstacles, i, num_flocks);
+        commands
+            .spawn_bundle(PbrBundle {
+                transform,
+                material: assets.materials.get(flock.0).cloned().unwrap_or_default(),
+                mesh: assets.mesh_3d.clone(),
+                ..Default::default()
+            })
+            .insert(boid)
+            .insert(velocity)
+            .insert(flock)
+            .insert(Name::new(format!("Bird {}", i)));
+    }
+}
+
+/// Simulation components for boid `i`, draws from the rng in a fixed order. Boids start at
+/// least `collision_radius` from the walls and obstacles so they don't begin stuck in them
+pub(super) fn boid_state_3d(
+    rng: &mut BoidRng,
+    config: &BoidConfig,
+    obstacles: &[(Mat4, Shape)],
+    i: usize,
+    num_flocks: usize,
+) -> (Transform, Boid3d, Velocity3d, Flock) {
+    /// Gives up on finding a clear spot when the obstacles fill the box
+    const SPAWN_ATTEMPTS: usize = 100;
+
+    let mut random_pm = |v: f32| rng.0.gen::<f32>() * 2. * v - v;
+    let margin = config.collision_radius;
+    let bounds = (config.bounds - Vec3::splat(margin)).max(Vec3::ZERO);
+    let mut position = Vec3::ZERO;
+    for _ in 0..SPAWN_ATTEMPTS {
+        position = Vec3::new(
+            random_pm(bounds.x),
+            random_pm(bounds.y),
+            random_pm(bounds.z),
+        );
+        if !near_obstacle(obstacles, position, margin) {
+            break;
+        }
+    }
+    let transform = Transform::from_translation(position);
+    // Uniform direction on the sphere
+    let z = random_pm(1.);
+    let angle = rng.0.gen::<f32>() * std::f32::consts::TAU;
+    let r = (1. - z * z).sqrt();
+    let dir = Vec3::new(r * angle.cos(), r * angle.sin(), z);
+    (
+        transform,
+        Boid3d::default(),
+        Velocity3d(dir * config.min_velocity),
+        Flock(i % num_flocks.max(1)),
+    )
+}
+
+// System to calculate each boids forces
+fn flocking_3d(
+    pool: Res<ComputeTaskPool>,
+    mut grid: Local<SpatialGrid>,
+    mut query: Query<(Entity, &mut Boid3d, &Transform, &Velocity3d, &Flock)>,
+    shapes: Query<(&ShapeInstance, &GlobalTransform)>,
+    config: Res<BoidConfig>,
+) {
+    // Snapshot every boid so forces don't depend on update order
+    let mut indices = HashMap::default();
+    let agents = query
+        .iter()
+        .enumerate()
+        .map(|(i, (entity, _, transform, velocity, flock))| {
+            indices.insert(entity, i);
+            Agent {
+                position: transform.translation,
+                velocity: velocity.0,
+                flock: flock.0,
+            }
+        })
+        .collect::<Vec<_>>();
+    grid.rebuild(config.neighbor_radius, &agents);
+
+    // Rays are tested in each shape's local space
+    let obstacles = shapes
+        .iter()
+        .map(|(shape, transform)| (transform.compute_matrix().inverse(), shape.value))
+        .collect::<Vec<_>>();
+    let directions = avoidance_directions();
+    let params = config.flocking_params();
+    let (collision_radius, collision_avoidance_force, bounds) = (
+        config.collision_radius,
+        config.collision_avoidance_force,
+        config.bounds,
+    );
+    let (agents, grid, obstacles, directions) = (&agents, &*grid, &obstacles, &directions);
+
+    let forces = par_forces(&pool, agents.len(), 64, |index, candidates| {
+        grid.candidates(agents[index].position, params.neighbor_radius, candidates);
+        let mut acceleration = flocking_force(agents, index, candidates.iter().copied(), &params);
+
+        let pos_a = agents[index].position;
+        let velocity_a = agents[index].velocity;
+        let dir_a = velocity_a.normalize();
+        let blocked =
+            |dir: Vec3| ray_blocked(obstacles, bounds, pos_a, pos_a + dir * collision_radius);
+        if blocked(dir_a) {
+            let to_heading = Quat::from_rotation_arc(Vec3::Z, dir_a);
+            let direction = directions
+                .iter()
+                .map(|&dir| to_heading * dir)
+                .find(|&dir| !blocked(dir))
+                .unwrap_or(dir_a);
+
+            acceleration += steer(
+                velocity_a,
+                direction,
+                params.max_velocity,
+                params.max_acceleration,
+            ) * collision_avoidance_force;
+        }
+        acceleration
+    });
+    query.for_each_mut(|(entity, mut boid, _, _, _)| boid.force = forces[indices[&entity]]);
+}
+
+// System to apply the boid force to the velocity, rotation and position
+fn heading_3d(
+    mut query: Query<(&Boid3d, &mut Velocity3d, &mut Transform)>,
+    config: Res<BoidConfig>,
+) {
+    query.for_each_mut(|(boid, mut velocity, mut transform)| {
+        velocity.0 += boid.force * TIME_STEP;
+        velocity.0 = velocity
+            .0
+            .clamp_length(config.min_velocity, config.max_velocity);
+
+        // The capsule mesh points along Y
+        transform.rotation = Quat::from_rotation_arc(Vec3::Y, velocity.0.normalize());
+        transform.translation += velocity.0 * TIME_STEP;
+
+        // Anything that slips past the avoidance bounces off the walls
+        let bounds = config.bounds;
+        for axis in 0..3 {
+            if transform.translation[axis].abs() > bounds[axis] {
+                transform.translation[axis] =
+                    transform.translation[axis].clamp(-bounds[axis], bounds[axis]);
+                velocity.0[axis] = -velocity.0[axis];
+            }
+        }
+    });
+}
+
+/// Unit directions spiralling out from +Z, so the first clear one is the smallest turn
+fn avoidance_directions() -> Vec<Vec3> {
+    let golden_ratio = (1. + 5f32.sqrt()) / 2.;
+    (0..AVOIDANCE_RAYS)
+        .map(|i| {
+            let inclination = (1. - 2. * i as f32 / AVOIDANCE_RAYS as f32).acos();
+            let azimuth = std::f32::consts::TAU * golden_ratio * i as f32;
+            Vec3::new(
+                inclination.sin() * azimuth.cos(),
+                inclination.sin() * azimuth.sin(),
+                inclination.cos(),
+            )
+        })
+        .collect()
+}
+
+/// Does the segment leave the box or touch any obstacle
+fn ray_blocked(obstacles: &[(Mat4, Shape)], bounds: Vec3, start: Vec3, end: Vec3) -> bool {
+    end.abs().cmpgt(bounds).any()
+        || obstacles.iter().any(|(world_to_local, shape)| {
+            segment_hits_shape(
+                shape,
+                world_to_local.transform_point3(start),
+                world_to_local.transform_point3(end),
+            )
+        })
+}
+
+/// Is any obstacle within `distance` of `position` along an axis
+fn near_obstacle(obstacles: &[(Mat4, Shape)], position: Vec3, distance: f32) -> bool {
+    [Vec3::X, Vec3::Y, Vec3::Z].iter().any(|axis| {
+        let (start, end) = (position - *axis * distance, position + *axis * distance);
+        obstacles.iter().any(|(world_to_local, shape)| {
+            segment_hits_shape(
+                shape,
+                world_to_local.transform_point3(start),
+                world_to_local.transform_point3(end),
+            )
+        })
+    })
+}
+
+fn segment_hits_shape(shape: &Shape, a: Vec3, b: Vec3) -> bool {
+    match shape {
+        Shape::Icosphere(sphere) => {
+            closest_on_segment(a, b, Vec3::ZERO).length_squared() <= sphere.radius * sphere.radius
+        }
+        Shape::Capsule(capsule) => {
+            let half = Vec3::Y * capsule.depth / 2.;
+            segment_segment_distance_squared(a, b, -half, half) <= capsule.radius * capsule.radius
+        }
+        Shape::Quad(quad) => plane_crossing(a, b, 2).map_or(false, |p| {
+            p.x.abs() <= quad.size.x / 2. && p.y.abs() <= quad.size.y / 2.
+        }),
+        Shape::Plane(plane) => plane_crossing(a, b, 1).map_or(false, |p| {
+            p.x.abs() <= plane.size / 2. && p.z.abs() <= plane.size / 2.
+        }),
+    }
+}
+
+fn closest_on_segment(a: Vec3, b: Vec3, p: Vec3) -> Vec3 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0. {
+        ((p - a).dot(ab) / ab.length_squared()).clamp(0., 1.)
+    } else {
+        0.
+    };
+    a + ab * t
+}
+
+/// Where the segment crosses the plane through the origin normal to `axis`
+fn plane_crossing(a: Vec3, b: Vec3, axis: usize) -> Option<Vec3> {
+    if (a[axis] > 0.) == (b[axis] > 0.) || a[axis] == b[axis] {
+        return None;
+    }
+    let t = a[axis] / (a[axis] - b[axis]);
+    Some(a + (b - a) * t)
+}
+
+// Real-Time Collision Detection 5.1.9
+fn segment_segment_distance_squared(p1: Vec3, q1: Vec3, p2: Vec3, q2: Vec3) -> f32 {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+
+    let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+        (0., 0.)
+    } else if a <= f32::EPSILON {
+        (0., (f / e).clamp(0., 1.))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0., 1.), 0.)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let mut s = if denom != 0. {
+                ((b * f - c * e) / denom).clamp(0., 1.)
+            } else {
+                0.
+            };
+            let mut t = (b * s + f) / e;
+            if t < 0. {
+                t = 0.;
+                s = (-c / a).clamp(0., 1.);
+            } else if t > 1. {
+                t = 1.;
+                s = ((b - c) / a).clamp(0., 1.);
+            }
+            (s, t)
+        }
+    };
+    (p1 + d1 * s).distance_squared(p2 + d2 * t)
+}
@@ -0,0 +1,670 @@
+// Flocking math kept free of ECS types so it can be benchmarked and checked headless, generic
+// over Vec2 and Vec3 so the 2D and 3D modes steer the same way
+use bevy::{math::IVec3, prelude::*, tasks::TaskPool, utils::HashMap};
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+/// What the flocking rules need from a position or velocity
+pub trait FlockVector:
+    Copy
+    + Default
+    + PartialEq
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<f32, Output = Self>
+    + Div<f32, Output = Self>
+    + AddAssign
+    + SubAssign
+{
+    const ZERO: Self;
+
+    fn splat(v: f32) -> Self;
+    fn dot(self, other: Self) -> f32;
+    fn length_squared(self) -> f32;
+    fn normalize(self) -> Self;
+    fn clamp_length_max(self, max: f32) -> Self;
+    /// Is `other` inside the field of vision of something at `self` heading along `dir`
+    fn in_view(self, dir: Self, other: Self, field_of_vision: f32) -> bool;
+    /// Grid cell, 2D positions all land on z = 0
+    fn cell(self, cell_size: f32) -> IVec3;
+}
+
+impl FlockVector for Vec2 {
+    const ZERO: Self = Vec2::ZERO;
+
+    fn splat(v: f32) -> Self {
+        Vec2::splat(v)
+    }
+    fn dot(self, other: Self) -> f32 {
+        Vec2::dot(self, other)
+    }
+    fn length_squared(self) -> f32 {
+        Vec2::length_squared(self)
+    }
+    fn normalize(self) -> Self {
+        Vec2::normalize(self)
+    }
+    fn clamp_length_max(self, max: f32) -> Self {
+        Vec2::clamp_length_max(self, max)
+    }
+    // The original 2D test, kept as is so seeded runs still reproduce
+    fn in_view(self, dir: Self, other: Self, field_of_vision: f32) -> bool {
+        (self + dir).angle_between(other) < field_of_vision / 2.
+    }
+    fn cell(self, cell_size: f32) -> IVec3 {
+        (self / cell_size).floor().as_ivec2().extend(0)
+    }
+}
+
+impl FlockVector for Vec3 {
+    const ZERO: Self = Vec3::ZERO;
+
+    fn splat(v: f32) -> Self {
+        Vec3::splat(v)
+    }
+    fn dot(self, other: Self) -> f32 {
+        Vec3::dot(self, other)
+    }
+    fn length_squared(self) -> f32 {
+        Vec3::length_squared(self)
+    }
+    fn normalize(self) -> Self {
+        Vec3::normalize(self)
+    }
+    fn clamp_length_max(self, max: f32) -> Self {
+        Vec3::clamp_length_max(self, max)
+    }
+    // View cone around the heading
+    fn in_view(self, dir: Self, other: Self, field_of_vision: f32) -> bool {
+        let offset = other - self;
+        let length = offset.length();
+        length == 0. || dir.dot(offset) / length > (field_of_vision / 2.).cos()
+    }
+    fn cell(self, cell_size: f32) -> IVec3 {
+        (self / cell_size).floor().as_ivec3()
+    }
+}
+
+/// The parts of `BoidConfig` the neighbor forces need
+#[derive(Clone, Copy, Debug)]
+pub struct FlockingParams {
+    pub max_velocity: f32,
+    pub max_acceleration: f32,
+    pub neighbor_radius: f32,
+    pub field_of_vision: f32,
+    pub avoid_radius: f32,
+    pub interflock_separation_force: f32,
+    pub separation_force: f32,
+    pub align_force: f32,
+    pub cohesion_force: f32,
+    pub target_force: f32,
+}
+
+/// Snapshot of a boid taken before forces are computed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Agent<V = Vec2> {
+    pub position: V,
+    pub velocity: V,
+    pub flock: usize,
+}
+
+pub fn steer<V: FlockVector>(
+    current_vel: V,
+    dir: V,
+    max_velocity: f32,
+    max_acceleration: f32,
+) -> V {
+    (dir * max_velocity - current_vel).clamp_length_max(max_acceleration)
+}
+
+pub fn percieve<V: FlockVector>(
+    pos: V,
+    dir: V,
+    other: V,
+    neighbor_radius: f32,
+    field_of_vision: f32,
+) -> bool {
+    (other - pos).length_squared() < neighbor_radius * neighbor_radius
+        && pos.in_view(dir, other, field_of_vision)
+}
+
+/// Steer away from every predator within `radius`, closer ones count for more
+pub fn flee_force<V: FlockVector>(
+    position: V,
+    velocity: V,
+    predators: &[V],
+    radius: f32,
+    max_velocity: f32,
+    max_acceleration: f32,
+) -> V {
+    let away = predators
+        .iter()
+        .map(|&predator| position - predator)
+        .filter(|offset| offset.length_squared() < radius * radius)
+        .map(|offset| offset / offset.length_squared().max(f32::EPSILON))
+        .fold(V::ZERO, |total, away| total + away);
+    if away == V::ZERO {
+        return V::ZERO;
+    }
+    steer(velocity, away.normalize(), max_velocity, max_acceleration)
+}
+
+/// Copies of agents near the edges of a wrapping world, shifted across the seam so neighbor
+/// searches see boids on the other side. Appended after the originals so their indices hold,
+/// returns the index of the original each ghost copies
+pub fn add_wrap_ghosts(agents: &mut Vec<Agent>, size: Vec2, margin: f32) -> Vec<usize> {
+    let half = size / 2.;
+    let mut origins = Vec::new();
+    for i in 0..agents.len() {
+        let agent = agents[i];
+        let shift = |pos: f32, half: f32, size: f32| {
+            if pos < -half + margin {
+                size
+            } else if pos > half - margin {
+                -size
+            } else {
+                0.
+            }
+        };
+        let shift = Vec2::new(
+            shift(agent.position.x, half.x, size.x),
+            shift(agent.position.y, half.y, size.y),
+        );
+        let mut offsets = Vec::with_capacity(3);
+        if shift.x != 0. {
+            offsets.push(Vec2::new(shift.x, 0.));
+        }
+        if shift.y != 0. {
+            offsets.push(Vec2::new(0., shift.y));
+        }
+        if shift.x != 0. && shift.y != 0. {
+            offsets.push(shift);
+        }
+        for offset in offsets {
+            agents.push(Agent {
+                position: agent.position + offset,
+                ..agent
+            });
+            origins.push(i);
+        }
+    }
+    origins
+}
+
+/// Drops `index`'s own ghosts from `candidates` and keeps only the closest copy of every other
+/// agent. Worlds smaller than two neighbor radii can have an agent and its ghost both in range
+pub fn drop_ghost_duplicates(
+    agents: &[Agent],
+    index: usize,
+    origins: &[usize],
+    candidates: &mut Vec<usize>,
+) {
+    let count = agents.len() - origins.len();
+    if candidates.iter().all(|&j| j < count) {
+        return;
+    }
+    let origin = |j: usize| if j < count { j } else { origins[j - count] };
+    let distance = |j: usize| agents[j].position.distance_squared(agents[index].position);
+    let all = candidates.clone();
+    candidates.retain(|&j| {
+        origin(j) != index
+            && !all
+                .iter()
+                .any(|&k| k != j && origin(k) == origin(j) && (distance(k), k) < (distance(j), j))
+    });
+}
+
+/// Shortest offset in a world that wraps every `size`
+pub fn wrapped_offset(offset: Vec2, size: Vec2) -> Vec2 {
+    offset - size * (offset / size).round()
+}
+
+/// Steer back inside `half_size` once closer than `margin` to an edge
+pub fn containment_force(
+    position: Vec2,
+    velocity: Vec2,
+    half_size: Vec2,
+    margin: f32,
+    max_velocity: f32,
+    max_acceleration: f32,
+) -> Vec2 {
+    let inward = |pos: f32, half: f32| {
+        if pos > half - margin {
+            -1.
+        } else if pos < -half + margin {
+            1.
+        } else {
+            0.
+        }
+    };
+    let dir = Vec2::new(
+        inward(position.x, half_size.x),
+        inward(position.y, half_size.y),
+    );
+    if dir == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+    steer(velocity, dir.normalize(), max_velocity, max_acceleration)
+}
+
+/// Uniform grid over agent positions, rebuilt every frame with cells the size of the
+/// neighbor radius so a query only has to look at the surrounding 3x3 (3x3x3 in 3D) cells
+#[derive(Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<usize>>,
+    /// Lowest and highest cell with an agent in it
+    occupied: Option<(IVec3, IVec3)>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            occupied: None,
+        }
+    }
+
+    fn cell<V: FlockVector>(&self, pos: V) -> IVec3 {
+        pos.cell(self.cell_size)
+    }
+
+    /// Clear and insert every agent, cell vectors are kept to avoid reallocating
+    pub fn rebuild<V: FlockVector>(&mut self, cell_size: f32, agents: &[Agent<V>]) {
+        self.cell_size = cell_size.max(f32::EPSILON);
+        for indices in self.cells.values_mut() {
+            indices.clear();
+        }
+        self.occupied = None;
+        for (i, agent) in agents.iter().enumerate() {
+            let cell = self.cell(agent.position);
+            self.cells.entry(cell).or_default().push(i);
+            self.occupied = Some(match self.occupied {
+                Some((min, max)) => (min.min(cell), max.max(cell)),
+                None => (cell, cell),
+            });
+        }
+    }
+
+    /// Indices of agents that may be within `radius` of `pos`, in ascending order so
+    /// sums come out the same as iterating every agent
+    pub fn candidates<V: FlockVector>(&self, pos: V, radius: f32, out: &mut Vec<usize>) {
+        out.clear();
+        let min = self.cell(pos - V::splat(radius));
+        let max = self.cell(pos + V::splat(radius));
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    if let Some(indices) = self.cells.get(&IVec3::new(x, y, z)) {
+                        out.extend_from_slice(indices);
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+    }
+
+    /// Closest agent to `pos` other than `exclude` and how far it is. Looks through rings of
+    /// cells further and further out, stopping once nothing unchecked can be closer, so loners
+    /// don't have to check every agent
+    pub fn nearest<V: FlockVector>(
+        &self,
+        agents: &[Agent<V>],
+        pos: V,
+        exclude: usize,
+    ) -> Option<(usize, f32)> {
+        let (min, max) = self.occupied?;
+        let center = self.cell(pos);
+        let reach = (center - min).abs().max((max - center).abs()).max_element();
+        let mut nearest: Option<(usize, f32)> = None;
+        for ring in 0..=reach {
+            // Only the cells on the ring's shell, clamped to the occupied cells
+            let low = (center - IVec3::splat(ring)).max(min);
+            let high = (center + IVec3::splat(ring)).min(max);
+            for z in low.z..=high.z {
+                for y in low.y..=high.y {
+                    let on_shell = (z - center.z).abs() == ring || (y - center.y).abs() == ring;
+                    for x in low.x..=high.x {
+                        if !on_shell && (x - center.x).abs() != ring {
+                            continue;
+                        }
+                        let indices = match self.cells.get(&IVec3::new(x, y, z)) {
+                            Some(indices) => indices,
+                            None => continue,
+                        };
+                        for &j in indices.iter().filter(|&&j| j != exclude) {
+                            let distance = (agents[j].position - pos).length_squared().sqrt();
+                            match nearest {
+                                Some((k, nearest)) if (nearest, k) < (distance, j) => {}
+                                _ => nearest = Some((j, distance)),
+                            }
+                        }
+                    }
+                }
+            }
+            // Every cell past this ring is at least `ring` cells away from `pos`
+            match nearest {
+                Some((_, distance)) if distance < ring as f32 * self.cell_size => break,
+                _ => {}
+            }
+        }
+        nearest
+    }
+}
+
+/// Separation, alignment, cohesion, interflock avoidance and the pull to the center for
+/// agent `index`, `neighbors` must be in ascending order
+pub fn flocking_force<V: FlockVector>(
+    agents: &[Agent<V>],
+    index: usize,
+    neighbors: impl IntoIterator<Item = usize>,
+    params: &FlockingParams,
+) -> V {
+    let agent = &agents[index];
+    let pos_a = agent.position;
+    let velocity_a = agent.velocity;
+    let dir_a = velocity_a.normalize();
+    let mut acceleration = V::ZERO;
+    let mut flockmate_count = 0;
+    let mut foreign_count = 0;
+    let mut pos_total = V::ZERO;
+    let mut heading_total = V::ZERO;
+    let mut avoidance_total = V::ZERO;
+    let mut interflock_avoidance_total = V::ZERO;
+
+    for j in neighbors {
+        if j == index {
+            continue;
+        }
+        let other = &agents[j];
+        let pos_b = other.position;
+        let direction_b = other.velocity.normalize();
+        if percieve(
+            pos_a,
+            dir_a,
+            pos_b,
+            params.neighbor_radius,
+            params.field_of_vision,
+        ) {
+            let offset = pos_b - pos_a;
+            let sqr_dist = offset.length_squared();
+            if agent.flock == other.flock {
+                flockmate_count += 1;
+                pos_total += pos_b;
+                heading_total += direction_b;
+                if sqr_dist < params.avoid_radius * params.avoid_radius {
+                    avoidance_total -= offset / sqr_dist;
+                }
+            } else {
+                foreign_count += 1;
+                interflock_avoidance_total -= offset / sqr_dist;
+            }
+        }
+    }
+
+    if foreign_count > 0 {
+        let foreign_count = foreign_count as f32;
+        let avg_interflock_avoidance = interflock_avoidance_total / foreign_count;
+        acceleration += steer(
+            velocity_a,
+            avg_interflock_avoidance,
+            params.max_velocity,
+            params.max_acceleration,
+        ) * params.interflock_separation_force;
+    }
+
+    if flockmate_count > 0 {
+        let flockmate_count = flockmate_count as f32;
+        let avg_pos = pos_total / flockmate_count;
+        let avg_avoidance = avoidance_total / flockmate_count;
+        let avg_heading = heading_total / flockmate_count;
+        let offset_to_avg_pos = avg_pos - pos_a;
+
+        acceleration += steer(
+            velocity_a,
+            avg_avoidance,
+            params.max_velocity,
+            params.max_acceleration,
+        ) * params.separation_force;
+        acceleration += steer(
+            velocity_a,
+            offset_to_avg_pos,
+            params.max_velocity,
+            params.max_acceleration,
+        ) * params.cohesion_force;
+        acceleration += steer(
+            velocity_a,
+            avg_heading,
+            params.max_velocity,
+            params.max_acceleration,
+        ) * params.align_force;
+    }
+    let target_pos = V::ZERO;
+    acceleration += steer(
+        velocity_a,
+        target_pos - pos_a,
+        params.max_velocity,
+        params.max_acceleration,
+    ) * params.target_force;
+
+    acceleration
+}
+
+/// Checks every pair, the reference the grid has to match
+pub fn brute_force_forces<V: FlockVector>(agents: &[Agent<V>], params: &FlockingParams) -> Vec<V> {
+    (0..agents.len())
+        .map(|i| flocking_force(agents, i, 0..agents.len(), params))
+        .collect()
+}
+
+/// Runs `force` for the first `count` agents in batches on `pool`, each batch reuses one
+/// candidates buffer. Forces come back in agent order
+pub fn par_forces<V, F>(pool: &TaskPool, count: usize, batch_size: usize, force: F) -> Vec<V>
+where
+    V: FlockVector + 'static,
+    F: Fn(usize, &mut Vec<usize>) -> V + Send + Sync,
+{
+    let force = &force;
+    pool.scope(|scope| {
+        for start in (0..count).step_by(batch_size.max(1)) {
+            let end = (start + batch_size).min(count);
+            scope.spawn(async move {
+                let mut candidates = Vec::new();
+                (start..end)
+                    .map(|i| force(i, &mut candidates))
+                    .collect::<Vec<_>>()
+            });
+        }
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+pub fn grid_forces<V: FlockVector>(
+    agents: &[Agent<V>],
+    grid: &SpatialGrid,
+    params: &FlockingParams,
+) -> Vec<V> {
+    let mut candidates = Vec::new();
+    (0..agents.len())
+        .map(|i| {
+            grid.candidates(agents[i].position, params.neighbor_radius, &mut candidates);
+            flocking_force(agents, i, candidates.iter().copied(), params)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Same values as BoidConfig's defaults
+    const PARAMS: FlockingParams = FlockingParams {
+        max_velocity: 16.,
+        max_acceleration: 6.,
+        neighbor_radius: 20.,
+        field_of_vision: (2. / 3.) * std::f32::consts::TAU,
+        avoid_radius: 5.,
+        interflock_separation_force: 15.,
+        separation_force: 10.,
+        align_force: 1.,
+        cohesion_force: 1.,
+        target_force: 0.001,
+    };
+
+    fn agents_2d(seed: u64, count: usize, half_size: Vec2) -> Vec<Agent> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|i| {
+                let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+                Agent {
+                    position: Vec2::new(
+                        rng.gen_range(-half_size.x..half_size.x),
+                        rng.gen_range(-half_size.y..half_size.y),
+                    ),
+                    velocity: Vec2::new(angle.cos(), angle.sin()) * 4.,
+                    flock: i % 9,
+                }
+            })
+            .collect()
+    }
+
+    fn grid_matches_brute_force<V: FlockVector + std::fmt::Debug>(agents: &[Agent<V>]) {
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(PARAMS.neighbor_radius, agents);
+        let grid_result = grid_forces(agents, &grid, &PARAMS);
+        let brute_result = brute_force_forces(agents, &PARAMS);
+        // Same neighbors summed in the same order, so not even rounding differs
+        for (i, (grid, brute)) in grid_result.iter().zip(&brute_result).enumerate() {
+            assert!(
+                grid == brute,
+                "agent {}: grid {:?}, brute force {:?}",
+                i,
+                grid,
+                brute
+            );
+        }
+        assert!(grid_result.iter().any(|&force| force != V::ZERO));
+    }
+
+    #[test]
+    fn grid_matches_brute_force_2d() {
+        grid_matches_brute_force(&agents_2d(0, 1_000, Vec2::splat(80.)));
+    }
+
+    #[test]
+    fn grid_matches_brute_force_with_wrap_ghosts() {
+        let size = Vec2::new(142., 80.);
+        let mut agents = agents_2d(1, 500, size / 2.);
+        let count = agents.len();
+        add_wrap_ghosts(&mut agents, size, PARAMS.neighbor_radius);
+        assert!(agents.len() > count, "no agents near the edges");
+        grid_matches_brute_force(&agents);
+    }
+
+    #[test]
+    fn small_wrapped_worlds_see_each_agent_once() {
+        // Smaller than two neighbor radii, so ghosts overlap the originals
+        let size = Vec2::splat(30.);
+        let mut agents = agents_2d(4, 8, size / 2.);
+        let count = agents.len();
+        let origins = add_wrap_ghosts(&mut agents, size, PARAMS.neighbor_radius);
+        let origin = |j: usize| if j < count { j } else { origins[j - count] };
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(PARAMS.neighbor_radius, &agents);
+        let mut candidates = Vec::new();
+        let mut dropped = false;
+        for (index, agent) in agents.iter().enumerate().take(count) {
+            grid.candidates(agent.position, PARAMS.neighbor_radius, &mut candidates);
+            let before = candidates.len();
+            drop_ghost_duplicates(&agents, index, &origins, &mut candidates);
+            dropped |= candidates.len() < before;
+
+            let seen = candidates.iter().map(|&j| origin(j)).collect::<Vec<_>>();
+            assert!(!seen.contains(&index), "agent {} sees itself", index);
+            let unique = seen.iter().collect::<std::collections::HashSet<_>>();
+            assert_eq!(
+                unique.len(),
+                seen.len(),
+                "agent {} sees a copy twice",
+                index
+            );
+            // The copy kept is the closest one
+            for &j in &candidates {
+                let closest = (0..agents.len())
+                    .filter(|&k| origin(k) == origin(j))
+                    .map(|k| agents[k].position.distance(agent.position))
+                    .fold(f32::INFINITY, f32::min);
+                assert_eq!(agents[j].position.distance(agent.position), closest);
+            }
+            assert!(candidates.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        assert!(dropped, "no duplicates to drop");
+    }
+
+    #[test]
+    fn grid_matches_brute_force_3d() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let agents = (0..1_000)
+            .map(|i| Agent {
+                position: Vec3::new(
+                    rng.gen_range(-60.0..60.0),
+                    rng.gen_range(-40.0..40.0),
+                    rng.gen_range(-60.0..60.0),
+                ),
+                velocity: Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 4. - Vec3::splat(2.),
+                flock: i % 9,
+            })
+            .collect::<Vec<_>>();
+        grid_matches_brute_force(&agents);
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut agents = agents_2d(3, 200, Vec2::splat(80.));
+        // Loners far outside the neighbor radius of anyone
+        agents.push(Agent {
+            position: Vec2::new(400., -300.),
+            ..Default::default()
+        });
+        agents.push(Agent {
+            position: Vec2::new(-250., 500.),
+            ..Default::default()
+        });
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(PARAMS.neighbor_radius, &agents);
+        for (i, agent) in agents.iter().enumerate() {
+            let brute = agents
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(j, other)| (j, agent.position.distance(other.position)))
+                .min_by(|(j, a), (k, b)| a.total_cmp(b).then(j.cmp(k)));
+            assert_eq!(
+                grid.nearest(&agents, agent.position, i),
+                brute,
+                "agent {}",
+                i
+            );
+        }
+        grid.rebuild(PARAMS.neighbor_radius, &agents[..1]);
+        assert_eq!(grid.nearest(&agents[..1], agents[0].position, 0), None);
+    }
+
+    #[test]
+    fn par_forces_keeps_agent_order() {
+        let pool = TaskPool::new();
+        let forces = par_forces(&pool, 150, 64, |i, candidates| {
+            candidates.push(i);
+            Vec2::splat(i as f32)
+        });
+        assert_eq!(
+            forces,
+            (0..150).map(|i| Vec2::splat(i as f32)).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// Runs the simulation without a window or renderer, so refactors can be checked against a golden
+// hash
+use bevy::{
+    ecs::system::CommandQueue,
+    prelude::*,
+    tasks::{ComputeTaskPool, TaskPool},
+};
+
+use super::*;
+
+/// Simulate `steps` fixed steps of `num_boids` boids spawned from `seed`, returns [`state_hash`].
+/// The world is `map_size` so no camera or window is needed
+pub fn run(seed: u64, num_boids: usize, steps: usize) -> u64 {
+    state_hash(&mut simulate(seed, num_boids, steps))
+}
+
+/// Like [`run`] but hands back the world, [`BoidStats`] has the samples taken along the way
+pub fn simulate(seed: u64, num_boids: usize, steps: usize) -> World {
+    let mut world = World::new();
+    world.insert_resource(ComputeTaskPool(TaskPool::new()));
+
+    let config = BoidConfig {
+        seed,
+        num_boids,
+        ..Default::default()
+    };
+    let mut rng = BoidRng::new(seed);
+    for i in 0..num_boids {
+        // Same flock count as the default materials
+        world
+            .spawn()
+            .insert_bundle(boid_state(&mut rng, &config, i, 9));
+    }
+    for _ in 0..config.num_predators {
+        world
+            .spawn()
+            .insert_bundle(predator_state(&mut rng, &config));
+    }
+
+    let mut queue = CommandQueue::default();
+    spawn_walls(&mut Commands::new(&mut queue, &world));
+    queue.apply(&mut world);
+
+    world.insert_resource(config);
+    world.insert_resource(rng);
+    world.insert_resource(BoidStats::default());
+
+    let mut stage = simulation_stage();
+    for _ in 0..steps {
+        stage.run(&mut world);
+    }
+    world
+}
+
+/// FNV-1a over every boid's position and velocity bits, in spawn order
+pub fn state_hash(world: &mut World) -> u64 {
+    let mut boids = world
+        .query_filtered::<(Entity, &Transform, &Velocity), With<Boid>>()
+        .iter(world)
+        .map(|(entity, transform, velocity)| (entity, transform.translation, velocity.0))
+        .collect::<Vec<_>>();
+    boids.sort_by_key(|(entity, ..)| *entity);
+
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for (_, position, velocity) in boids {
+        for value in [position.x, position.y, velocity.x, velocity.y] {
+            for byte in value.to_bits().to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 0;
+    const NUM_BOIDS: usize = 300;
+    // Ten seconds at the fixed time step
+    const STEPS: usize = 600;
+
+    #[test]
+    fn same_seed_same_run() {
+        assert_eq!(run(SEED, NUM_BOIDS, 120), run(SEED, NUM_BOIDS, 120));
+    }
+
+    /// `BLESS=1 cargo test -p boids` records the current hash, only do that when a behaviour
+    /// change is intended
+    #[test]
+    fn matches_golden_hash() {
+        let hash = format!("{:016x}", run(SEED, NUM_BOIDS, STEPS));
+        if std::env::var_os("BLESS").is_some() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/golden_hash.txt");
+            std::fs::write(path, format!("{}\n", hash)).unwrap();
+            return;
+        }
+        assert_eq!(
+            hash,
+            include_str!("../golden_hash.txt").trim(),
+            "the simulation changed, bless the new hash if that was intended"
+        );
+    }
+}
@@ -1,27 +1,50 @@
 #![allow(warnings)]
-use bevy::{prelude::*, render::camera::ScalingMode};
+mod flock3d;
+pub mod flocking;
+pub mod headless;
+mod obstacles;
+mod presets;
+pub mod stats;
+
+use bevy::{
+    core::FixedTimestep, ecs::schedule::StageLabel, prelude::*, render::camera::ScalingMode,
+    tasks::ComputeTaskPool, utils::HashMap,
+};
+use bevy_egui::{egui::Window, EguiContext};
 use bevy_inspector_egui::{Inspectable, InspectableRegistry};
 use engine::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use flock3d::*;
+use flocking::*;
+use obstacles::*;
+use presets::*;
+use stats::*;
+
+/// Seconds simulated per step, the simulation stage runs at this rate no matter the frame rate
+pub const TIME_STEP: f32 = 1.0 / 60.0;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct SimulationStage;
+
+const BOIDS_RESET: &str = "boids_reset";
+
 #[wasm_bindgen(start)]
 pub fn run() {
-    let mut app = App::new();
-    app
-        .add_plugin(EnginePlugin {
-            title: "Boids".to_string(),
-        })
-        .init_inspector_resource::<BoidConfig>()
-        .insert_inspector_resource::<ClearColor>(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
-        .insert_inspector_resource::<AmbientLight>(AmbientLight {
-            color: Color::WHITE,
-            brightness: 0.05,
-        })
+    let mut app = boids_app("Boids", 0.05, simulation_stage());
+    app.init_resource::<ObstacleEditor>()
+        .init_resource::<BoidStats>()
+        .add_editor_window(STATS_WINDOW)
+        .add_input_action(BOIDS_RESET, &[InputBinding::Key(KeyCode::R)])
+        .add_event::<ResetSimulation>()
         .add_startup_system(setup)
-        .add_system(boids_flocking_system.label("flocking"))
-        .add_system(boid_heading_system.label("heading").after("flocking"))
-        .add_system(ls_adjustment.before("flocking"))
-        .add_system(heading_system.after("heading"));
+        .add_system(reset_simulation.chain(reconcile_boids))
+        .add_system(follow_snap)
+        .add_system(simulation_window.with_run_criteria(run_if_editor))
+        .add_system(place_obstacles.with_run_criteria(run_if_editor))
+        .add_system(stats_window.with_run_criteria(run_if_editor));
 
     // registering custom component to be able to edit it in inspector
     let mut registry = app.world.get_resource_mut::<InspectableRegistry>().unwrap();
@@ -31,6 +54,58 @@ pub fn run() {
     app.run();
 }
 
+/// Flocks in a box with a perspective camera instead of on the screen plane
+#[wasm_bindgen]
+pub fn run_3d() {
+    let mut app = boids_app("Boids 3D", 0.3, simulation_stage_3d());
+    app.add_plugin(ShapePlugin)
+        .add_startup_system(setup_3d)
+        .run();
+}
+
+/// Engine, config and presets shared by both modes, `stage` runs every `TIME_STEP`
+fn boids_app(title: &str, ambient_brightness: f32, stage: SystemStage) -> App {
+    let mut app = App::new();
+    app.add_plugin(EnginePlugin {
+        title: title.to_string(),
+    })
+    .init_inspector_resource::<BoidConfig>()
+    .init_inspector_resource::<BoidAssets>()
+    .add_plugin(PresetsPlugin)
+    .insert_resource(BoidRng::new(BoidConfig::default().seed))
+    .insert_inspector_resource::<ClearColor>(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
+    .insert_inspector_resource::<AmbientLight>(AmbientLight {
+        color: Color::WHITE,
+        brightness: ambient_brightness,
+    })
+    .add_stage_after(
+        CoreStage::Update,
+        SimulationStage,
+        stage.with_run_criteria(FixedTimestep::step(TIME_STEP as f64)),
+    );
+    app
+}
+
+/// Flocking, steering and movement for one `TIME_STEP`, run headless by [`headless::run`]
+pub fn simulation_stage() -> SystemStage {
+    SystemStage::parallel()
+        .with_system(ls_adjustment.before("flocking"))
+        .with_system(boids_flocking_system.label("flocking"))
+        .with_system(boid_heading_system.label("heading").after("flocking"))
+        .with_system(predator_system.label("predators").after("heading"))
+        .with_system(heading_system.label("move").after("predators"))
+        .with_system(record_stats.after("move"))
+}
+
+/// The only source of randomness in the simulation, so a seed reproduces a run
+pub struct BoidRng(pub StdRng);
+
+impl BoidRng {
+    pub fn new(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
 // Heading component (velocity vector)
 #[derive(Component, Default)]
 pub struct Velocity(pub Vec2);
@@ -43,9 +118,9 @@ impl std::ops::Deref for Velocity {
     }
 }
 
-fn heading_system(time: Res<Time>, mut query: Query<(&Velocity, &mut Transform)>) {
+fn heading_system(mut query: Query<(&Velocity, &mut Transform)>) {
     query.for_each_mut(|(heading, mut transform)| {
-        transform.translation += heading.0.extend(0.) * time.delta_seconds();
+        transform.translation += heading.0.extend(0.) * TIME_STEP;
     });
 }
 
@@ -58,137 +133,119 @@ pub struct Boid {
 #[derive(Component, Inspectable, Default, PartialEq, Eq)]
 pub struct Flock(usize);
 
-fn steer(current_vel: Vec2, dir: Vec2, max_velocity: f32, max_acceleration: f32) -> Vec2 {
-    (dir * max_velocity - current_vel).clamp_length_max(max_acceleration)
-}
-
-fn percieve(pos: Vec2, dir: Vec2, other: Vec2, neighbor_radius: f32, field_of_vision: f32) -> bool {
-    pos.distance_squared(other) < neighbor_radius * neighbor_radius
-        && (pos + dir).angle_between(other) < field_of_vision / 2.
-}
+// Chases the nearest boid, boids within flee_radius run from it
+#[derive(Component, Default)]
+pub struct Predator;
 
 // System to calculate each boids forces
 fn boids_flocking_system(
-    mut query: Query<(&mut Boid, &Transform, &Velocity, Entity, &Flock)>,
-    inner_query: Query<(&Transform, &Velocity, Entity, &Flock), With<Boid>>, /* This query
-                                                                              * requires the
-                                                                              * Boid component
-                                                                              * but doesn't
-                                                                              * borrow it */
-    raycastable: Query<&Raycastable>,
+    pool: Res<ComputeTaskPool>,
+    mut grid: Local<SpatialGrid>,
+    mut query: Query<(Entity, &mut Boid, &Transform, &Velocity, &Flock)>,
+    raycastable: Query<(&Raycastable, Option<&Wall>)>,
+    predators: Query<&Transform, With<Predator>>,
     config: Res<BoidConfig>,
 ) {
-
-    query.for_each_mut(|(mut boid, transform_a, velocity_a, entity_a, flock_a)| {
-        let pos_a = transform_a.translation.truncate();
-        let velocity_a = velocity_a.0;
-        let dir_a = velocity_a.normalize();
-        let mut acceleration = Vec2::ZERO;
-        let mut flockmate_count = 0;
-        let mut foreign_count = 0;
-        let mut pos_total = Vec2::ZERO;
-        let mut heading_total = Vec2::ZERO;
-        let mut avoidance_total = Vec2::ZERO;
-        let mut interflock_avoidance_total = Vec2::ZERO;
-
-        inner_query.for_each(|(transform_b, velocity_b, entity_b, flock_b)| {
-            if entity_a == entity_b {
-                return;
+    // Snapshot every boid so forces don't depend on update order
+    let mut indices = HashMap::default();
+    let mut agents = query
+        .iter()
+        .enumerate()
+        .map(|(i, (entity, _, transform, velocity, flock))| {
+            indices.insert(entity, i);
+            Agent {
+                position: transform.translation.truncate(),
+                velocity: velocity.0,
+                flock: flock.0,
             }
-            let pos_b = transform_b.translation.truncate();
-            let direction_b = velocity_b.normalize();
-            if percieve(
+        })
+        .collect::<Vec<_>>();
+    let mut predators = predators
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect::<Vec<_>>();
+    let mut origins = Vec::new();
+    if config.boundary == BoundaryMode::Wrap {
+        let size = config.map_size;
+        origins = add_wrap_ghosts(&mut agents, size, config.neighbor_radius);
+        predators = predators
+            .iter()
+            .flat_map(|&p| {
+                (-1..=1).flat_map(move |x| {
+                    (-1..=1).map(move |y| p + Vec2::new(x as f32, y as f32) * size)
+                })
+            })
+            .collect();
+    }
+    grid.rebuild(config.neighbor_radius, &agents);
+
+    // The map edges only block in walls mode
+    let walls = raycastable
+        .iter()
+        .filter(|(_, wall)| wall.is_none() || config.boundary == BoundaryMode::Walls)
+        .map(|(raycastable, _)| raycastable.clone())
+        .collect::<Vec<_>>();
+    let (boundary, half_size) = (config.boundary, config.map_size / 2.);
+    let params = config.flocking_params();
+    let (collision_radius, turn_find_step, collision_avoidance_force) = (
+        config.collision_radius,
+        config.turn_find_step,
+        config.collision_avoidance_force,
+    );
+    let (flee_radius, flee_strength) = (config.flee_radius, config.flee_force);
+    let (agents, grid, walls, predators, origins) = (&agents, &*grid, &walls, &predators, &origins);
+
+    let forces = par_forces(&pool, indices.len(), 64, |index, candidates| {
+        grid.candidates(agents[index].position, params.neighbor_radius, candidates);
+        drop_ghost_duplicates(agents, index, origins, candidates);
+        let mut acceleration = flocking_force(agents, index, candidates.iter().copied(), &params);
+
+        let pos_a = agents[index].position;
+        let velocity_a = agents[index].velocity;
+        let dir_a = velocity_a.normalize();
+        acceleration += flee_force(
+            pos_a,
+            velocity_a,
+            predators,
+            flee_radius,
+            params.max_velocity,
+            params.max_acceleration,
+        ) * flee_strength;
+        if boundary == BoundaryMode::Steer {
+            acceleration += containment_force(
                 pos_a,
-                dir_a,
-                pos_b,
-                config.neighbor_radius,
-                config.field_of_vision,
-            ) {
-                let offset = pos_b - pos_a;
-                let sqr_dist = offset.length_squared();
-                if flock_a == flock_b {
-                    flockmate_count += 1;
-                    pos_total += pos_b;
-                    heading_total += direction_b;
-                    if sqr_dist < config.avoid_radius * config.avoid_radius {
-                        avoidance_total -= offset / sqr_dist;
-                    }
-                } else {
-                    foreign_count += 1;
-                    interflock_avoidance_total -= offset / sqr_dist;
-                }
-            }
-        });
-
-        if foreign_count > 0 {
-            let foreign_count = foreign_count as f32;
-            let avg_interflock_avoidance = interflock_avoidance_total / foreign_count;
-            acceleration += steer(
-                velocity_a,
-                avg_interflock_avoidance,
-                config.max_velocity,
-                config.max_acceleration,
-            ) * config.interflock_separation_force;
-        }
-
-        if flockmate_count > 0 {
-            let flockmate_count = flockmate_count as f32;
-            let avg_pos = pos_total / flockmate_count;
-            let avg_avoidance = avoidance_total / flockmate_count;
-            let avg_heading = heading_total / flockmate_count;
-            let offset_to_avg_pos = avg_pos - pos_a;
-
-            acceleration += steer(
-                velocity_a,
-                avg_avoidance,
-                config.max_velocity,
-                config.max_acceleration,
-            ) * config.separation_force;
-            acceleration += steer(
-                velocity_a,
-                offset_to_avg_pos,
-                config.max_velocity,
-                config.max_acceleration,
-            ) * config.cohesion_force;
-            acceleration += steer(
                 velocity_a,
-                avg_heading,
-                config.max_velocity,
-                config.max_acceleration,
-            ) * config.align_force;
+                half_size,
+                collision_radius,
+                params.max_velocity,
+                params.max_acceleration,
+            ) * collision_avoidance_force;
         }
-        let target_pos = Vec2::ZERO;
-        acceleration += steer(
-            velocity_a,
-            target_pos - pos_a,
-            config.max_velocity,
-            config.max_acceleration,
-        ) * config.target_force;
-
-        if raycast(raycastable.iter(), pos_a, dir_a * config.collision_radius) {
+        if raycast(walls, pos_a, dir_a * collision_radius) {
             let dir_angle = f32::atan2(dir_a.y, dir_a.x);
-            let direction = (0..(std::f32::consts::TAU / config.turn_find_step) as usize)
+            let direction = (0..(std::f32::consts::TAU / turn_find_step) as usize)
                 .map(|i| {
                     if i % 2 == 0 {
-                        config.turn_find_step * (i / 2) as f32
+                        turn_find_step * (i / 2) as f32
                     } else {
-                        -config.turn_find_step * ((i + 1) / 2) as f32
+                        -turn_find_step * ((i + 1) / 2) as f32
                     }
                 })
                 .map(|v| v + dir_angle)
                 .map(vec_from_angle)
-                .find(|&dir| !raycast(raycastable.iter(), pos_a, dir * config.collision_radius))
+                .find(|&dir| !raycast(walls, pos_a, dir * collision_radius))
                 .unwrap_or(dir_a);
 
             acceleration += steer(
                 velocity_a,
                 direction,
-                config.max_velocity,
-                config.max_acceleration,
-            ) * config.collision_avoidance_force;
+                params.max_velocity,
+                params.max_acceleration,
+            ) * collision_avoidance_force;
         }
-        boid.force = acceleration;
+        acceleration
     });
+    query.for_each_mut(|(entity, mut boid, _, _, _)| boid.force = forces[indices[&entity]]);
 }
 fn vec_from_angle(x: f32) -> Vec2 {
     Vec2::new(f32::cos(x), f32::sin(x))
@@ -201,6 +258,21 @@ fn raycast<'a>(
 ) -> bool {
     raycastable.into_iter().any(|r| match r {
         Raycastable::LS(ls) => segment_segment_intersection(*ls, LineSegment { start, offset }),
+        Raycastable::Circle { center, radius } => {
+            segment_circle_intersection(LineSegment { start, offset }, *center, *radius)
+        }
+        Raycastable::Polygon(points) => points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .any(|(&a, &b)| {
+                segment_segment_intersection(
+                    LineSegment {
+                        start: a,
+                        offset: b - a,
+                    },
+                    LineSegment { start, offset },
+                )
+            }),
     })
 }
 
@@ -209,43 +281,107 @@ struct MainCamera;
 
 // System to apply the boid force to the heading/rotation component
 fn boid_heading_system(
-    time: Res<Time>,
     mut query: Query<(&Boid, &mut Velocity, &mut Transform)>,
-    cam: Query<&OrthographicProjection, With<MainCamera>>,
     config: Res<BoidConfig>,
 ) {
     query.for_each_mut(|(boid, mut velocity, mut transform)| {
         let rotation = &mut transform.rotation;
 
         // Update the heading (velocity)
-        velocity.0 += boid.force * time.delta_seconds();
+        velocity.0 += boid.force * TIME_STEP;
         velocity.0 = velocity.0.clamp_length(config.min_velocity, config.max_velocity);
 
         // Compute the rotation according to the heading
         let angle = f32::atan2(velocity.y, velocity.x);
         *rotation = Quat::from_euler(EulerRot::YXZ, 0., 0., angle);
 
-        let pos = &mut transform.translation;
-        let proj = cam.single();
-        if pos.x < proj.left * proj.scale {
-            pos.x = proj.right * proj.scale
-        }
-        if pos.x > proj.right * proj.scale {
-            pos.x = proj.left * proj.scale
-        }
-        if pos.y < proj.bottom * proj.scale {
-            pos.y = proj.top * proj.scale
-        }
-        if pos.y > proj.top * proj.scale {
-            pos.y = proj.bottom * proj.scale
+        apply_boundary(&mut transform.translation, &mut velocity.0, &config);
+    });
+}
+
+// Steer predators toward the nearest boid
+fn predator_system(
+    mut predators: Query<(&mut Velocity, &mut Transform), (With<Predator>, Without<Boid>)>,
+    boids: Query<&Transform, (With<Boid>, Without<Predator>)>,
+    config: Res<BoidConfig>,
+) {
+    predators.for_each_mut(|(mut velocity, mut transform)| {
+        let pos = transform.translation.truncate();
+        // Chase across the seam when wrapping
+        let offset = |target: Vec2| match config.boundary {
+            BoundaryMode::Wrap => wrapped_offset(target - pos, config.map_size),
+            _ => target - pos,
+        };
+        let nearest = boids
+            .iter()
+            .map(|boid| offset(boid.translation.truncate()))
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        if let Some(offset) = nearest {
+            let force = steer(
+                velocity.0,
+                offset.normalize_or_zero(),
+                config.predator_max_velocity,
+                config.predator_max_acceleration,
+            );
+            velocity.0 += force * TIME_STEP;
+            velocity.0 = velocity.0.clamp_length_max(config.predator_max_velocity);
         }
+
+        let angle = f32::atan2(velocity.y, velocity.x);
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, 0., 0., angle);
+        apply_boundary(&mut transform.translation, &mut velocity.0, &config);
     });
 }
 
-#[derive(Inspectable)]
+/// How the edges of `map_size` treat boids
+#[derive(Inspectable, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum BoundaryMode {
+    /// Leaving one edge comes back in on the other, neighbors are found across the seam
+    Wrap,
+    /// The edges are raycast like obstacles and bounce anything that gets through
+    Walls,
+    /// Boids near an edge steer back in, nothing stops them leaving
+    Steer,
+}
+
+impl Default for BoundaryMode {
+    fn default() -> Self {
+        BoundaryMode::Walls
+    }
+}
+
+// Keep a position inside the map, the map is centered on the origin
+fn apply_boundary(pos: &mut Vec3, velocity: &mut Vec2, config: &BoidConfig) {
+    let half = config.map_size / 2.;
+    for axis in 0..2 {
+        match config.boundary {
+            BoundaryMode::Wrap => {
+                pos[axis] = (pos[axis] + half[axis]).rem_euclid(config.map_size[axis]) - half[axis];
+            }
+            BoundaryMode::Walls => {
+                if pos[axis].abs() > half[axis] {
+                    pos[axis] = pos[axis].clamp(-half[axis], half[axis]);
+                    velocity[axis] = -velocity[axis].abs() * pos[axis].signum();
+                }
+            }
+            BoundaryMode::Steer => {}
+        }
+    }
+}
+
+#[derive(Inspectable, Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct BoidConfig {
+    // Loads a tuning from assets/presets, or saves the current one
+    #[serde(skip)]
+    preset: PresetPicker,
+
     // The number of boids we will spawn
+    #[serde(skip)]
     num_boids: usize,
+    // Seed for spawning, the same seed gives the same run
+    #[serde(skip)]
+    seed: u64,
 
     // These constants influence the boid movement
     max_velocity: f32,
@@ -263,28 +399,32 @@ struct BoidConfig {
     collision_radius: f32,
     turn_find_step: f32,
 
-    #[inspectable(min = Vec2::new(0.0, 0.0))]
+    // Predators and how hard boids run from them
+    num_predators: usize,
+    predator_max_velocity: f32,
+    predator_max_acceleration: f32,
+    flee_radius: f32,
+    flee_force: f32,
+
+    // Size of the world, centered on the origin and independent of the window
+    boundary: BoundaryMode,
+    #[inspectable(min = Vec2::new(1.0, 1.0))]
     map_size: Vec2,
-    materials: Vec<Handle<StandardMaterial>>,
-    #[inspectable(ignore)]
-    mesh: Handle<Mesh>,
+    // Half size of the box the 3D mode flocks in
+    #[inspectable(min = Vec3::ONE)]
+    bounds: Vec3,
 }
 
-impl FromWorld for BoidConfig {
-    fn from_world(world: &mut World) -> Self {
-        let world = world.cell();
-
-        let mut materials = world
-            .get_resource_mut::<Assets<StandardMaterial>>()
-            .unwrap();
-
-        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
-
-        let asset_server = world.get_resource::<AssetServer>().unwrap();
-        let texture = asset_server.load("icon.png");
+impl Default for BoidConfig {
+    fn default() -> Self {
         Self {
-            map_size: Vec2::new(10.0, 10.0),
+            preset: PresetPicker::default(),
+            boundary: BoundaryMode::default(),
+            // Fills a 16:9 window at the camera's default zoom
+            map_size: Vec2::new(142.0, 80.0),
+            bounds: Vec3::new(60.0, 40.0, 60.0),
             num_boids: 300,
+            seed: 0,
             max_velocity: 16.,
             min_velocity: 4.,
             max_acceleration: 6.,
@@ -299,7 +439,57 @@ impl FromWorld for BoidConfig {
             collision_avoidance_force: 20.,
             collision_radius: 10.,
             turn_find_step: (std::f32::consts::PI / 180.) * 45.,
+            num_predators: 1,
+            predator_max_velocity: 20.,
+            predator_max_acceleration: 8.,
+            flee_radius: 15.,
+            flee_force: 30.,
+        }
+    }
+}
+
+impl BoidConfig {
+    fn flocking_params(&self) -> FlockingParams {
+        FlockingParams {
+            max_velocity: self.max_velocity,
+            max_acceleration: self.max_acceleration,
+            neighbor_radius: self.neighbor_radius,
+            field_of_vision: self.field_of_vision,
+            avoid_radius: self.avoid_radius,
+            interflock_separation_force: self.interflock_separation_force,
+            separation_force: self.separation_force,
+            align_force: self.align_force,
+            cohesion_force: self.cohesion_force,
+            target_force: self.target_force,
+        }
+    }
+}
+
+// Rendering only, kept out of BoidConfig so the simulation runs without assets
+#[derive(Inspectable)]
+struct BoidAssets {
+    // One flock per material
+    materials: Vec<Handle<StandardMaterial>>,
+    predator_material: Handle<StandardMaterial>,
+    #[inspectable(ignore)]
+    mesh: Handle<Mesh>,
+    #[inspectable(ignore)]
+    mesh_3d: Handle<Mesh>,
+}
 
+impl FromWorld for BoidAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+
+        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let texture = asset_server.load("icon.png");
+        Self {
             materials: [
                 Color::RED,
                 Color::GREEN,
@@ -321,15 +511,88 @@ impl FromWorld for BoidConfig {
                 })
             })
             .collect::<Vec<_>>(),
+            predator_material: materials.add(StandardMaterial {
+                base_color: Color::BLACK,
+                base_color_texture: Some(texture.clone()),
+                unlit: true,
+                ..Default::default()
+            }),
             mesh: meshes.add(Mesh::from(shape::Quad { size: Vec2::splat(1.0), flip: false  })),
+            mesh_3d: meshes.add(Mesh::from(Capsule {
+                radius: 0.3,
+                depth: 1.0,
+                latitudes: 8,
+                longitudes: 8,
+                ..Default::default()
+            })),
         }
     }
 }
 
-// Set up a scene
-fn setup(mut commands: Commands, config: Res<BoidConfig>, mut _meshes: ResMut<Assets<Mesh>>) {
+/// Simulation components for boid `i`, draws from the rng in a fixed order
+fn boid_state(
+    rng: &mut BoidRng,
+    config: &BoidConfig,
+    i: usize,
+    num_flocks: usize,
+) -> (Transform, Boid, Velocity, Flock) {
+    let mut random_pm = |v: f32| rng.0.gen::<f32>() * 2. * v - v;
+    let transform = Transform::from_xyz(random_pm(10.), random_pm(10.), -5.);
+    let angle = rng.0.gen::<f32>() * std::f32::consts::TAU;
+    (
+        transform,
+        Boid::default(),
+        Velocity(Vec2::new(angle.cos(), angle.sin()) * config.min_velocity),
+        Flock(i % num_flocks.max(1)),
+    )
+}
+
+/// Simulation components for a predator, drawn after the boids
+fn predator_state(rng: &mut BoidRng, config: &BoidConfig) -> (Transform, Predator, Velocity) {
+    let mut random_pm = |v: f32| rng.0.gen::<f32>() * 2. * v - v;
+    let transform =
+        Transform::from_xyz(random_pm(30.), random_pm(30.), -4.9).with_scale(Vec3::splat(2.));
+    let angle = rng.0.gen::<f32>() * std::f32::consts::TAU;
+    (
+        transform,
+        Predator,
+        Velocity(Vec2::new(angle.cos(), angle.sin()) * config.min_velocity),
+    )
+}
+
+/// Walls the boids steer away from in walls mode, kept on the map's edges by `ls_adjustment`
+fn spawn_walls(commands: &mut Commands) {
     commands
-        // Camera, follows the last boid
+        .spawn()
+        .insert(Raycastable::LS(LineSegment::default()))
+        .insert(Wall)
+        .insert(Left);
+    commands
+        .spawn()
+        .insert(Raycastable::LS(LineSegment::default()))
+        .insert(Wall)
+        .insert(Right);
+    commands
+        .spawn()
+        .insert(Raycastable::LS(LineSegment::default()))
+        .insert(Wall)
+        .insert(Top);
+    commands
+        .spawn()
+        .insert(Raycastable::LS(LineSegment::default()))
+        .insert(Wall)
+        .insert(Bottom);
+}
+
+// Set up a scene
+fn setup(
+    mut commands: Commands,
+    config: Res<BoidConfig>,
+    assets: Res<BoidAssets>,
+    mut rng: ResMut<BoidRng>,
+) {
+    // Camera, follows the last boid
+    let camera = commands
         .spawn_bundle(OrthographicCameraBundle {
             orthographic_projection: OrthographicProjection {
                 scale: 40.,
@@ -340,27 +603,27 @@ fn setup(mut commands: Commands, config: Res<BoidConfig>, mut _meshes: ResMut<As
         })
         //.spawn_bundle(PerspectiveCameraBundle::new_3d())
         .insert(MainCamera)
-        //.insert(CameraController::default())
-        .insert(Name::new("Camera"));
-
+        .insert(PanZoomCamera {
+            min_scale: 5.,
+            max_scale: 200.,
+            ..Default::default()
+        })
+        .insert(Name::new("Camera"))
+        .id();
 
-    let random_pm = |v: f32| rand::random::<f32>() * 2. * v - v;
+    let mut last_boid = None;
     for i in 0..config.num_boids {
-        commands
-            .spawn_bundle(PbrBundle {
-                transform: Transform::from_xyz(random_pm(10.), random_pm(10.), -5.),
-                material: config.materials[i % config.materials.len()].clone(),
-                mesh: config.mesh.clone(),
-                ..Default::default()
-            })
-            .insert(Boid::default())
-            .insert(Velocity({
-                let angle = rand::random::<f32>() * std::f32::consts::TAU;
-                Vec2::new(angle.cos(), angle.sin()) * config.min_velocity
-            }))
-            .insert(Flock(i % config.materials.len()))
-            .insert(Name::new(format!("Bird {}", i)));
+        last_boid = Some(spawn_boid(&mut commands, &mut rng, &config, &assets, i));
+    }
+    for i in 0..config.num_predators {
+        spawn_predator(&mut commands, &mut rng, &config, &assets, i);
     }
+    commands.entity(camera).insert(FollowCamera {
+        target: last_boid,
+        damping: 0.3,
+        look_ahead: 0.5,
+        ..FollowCamera::top_down()
+    });
     // commands.spawn_batch((0..NUM_BOIDS).map(move |i| {
     //     let handle = config.materials[i % config.materials.len()].clone();
     //     GroupBundle {
@@ -380,22 +643,170 @@ fn setup(mut commands: Commands, config: Res<BoidConfig>, mut _meshes: ResMut<As
     //     }
     // }));
 
+    spawn_walls(&mut commands);
+}
+
+fn spawn_boid(
+    commands: &mut Commands,
+    rng: &mut BoidRng,
+    config: &BoidConfig,
+    assets: &BoidAssets,
+    i: usize,
+) -> Entity {
+    let (transform, boid, velocity, flock) = boid_state(rng, config, i, assets.materials.len());
     commands
-        .spawn()
-        .insert(Raycastable::LS(LineSegment::default()))
-        .insert(Left);
-    commands
-        .spawn()
-        .insert(Raycastable::LS(LineSegment::default()))
-        .insert(Right);
-    commands
-        .spawn()
-        .insert(Raycastable::LS(LineSegment::default()))
-        .insert(Top);
+        .spawn_bundle(PbrBundle {
+            transform,
+            material: assets.materials.get(flock.0).cloned().unwrap_or_default(),
+            mesh: assets.mesh.clone(),
+            ..Default::default()
+        })
+        .insert(boid)
+        .insert(velocity)
+        .insert(flock)
+        .insert(FollowTarget)
+        .insert(Name::new(format!("Bird {}", i)))
+        .id()
+}
+
+fn spawn_predator(
+    commands: &mut Commands,
+    rng: &mut BoidRng,
+    config: &BoidConfig,
+    assets: &BoidAssets,
+    i: usize,
+) -> Entity {
+    let (transform, predator, velocity) = predator_state(rng, config);
     commands
-        .spawn()
-        .insert(Raycastable::LS(LineSegment::default()))
-        .insert(Bottom);
+        .spawn_bundle(PbrBundle {
+            transform,
+            material: assets.predator_material.clone(),
+            mesh: assets.mesh.clone(),
+            ..Default::default()
+        })
+        .insert(predator)
+        .insert(velocity)
+        .insert(FollowTarget)
+        .insert(Name::new(format!("Predator {}", i)))
+        .id()
+}
+
+/// Spawn or despawn birds to match `num_boids`, and reassign flocks when the materials change
+fn reconcile_boids(
+    In(reset): In<bool>,
+    mut commands: Commands,
+    config: Res<BoidConfig>,
+    assets: Res<BoidAssets>,
+    mut rng: ResMut<BoidRng>,
+    mut last_materials: Local<Vec<Handle<StandardMaterial>>>,
+    mut boids: Query<(Entity, &mut Flock, &mut Handle<StandardMaterial>), With<Boid>>,
+    predators: Query<Entity, With<Predator>>,
+) {
+    if !config.is_changed() && !assets.is_changed() {
+        return;
+    }
+    // The reset already spawned the whole flock from the current config
+    if reset {
+        *last_materials = assets.materials.clone();
+        return;
+    }
+
+    let mut predator_entities = predators.iter().collect::<Vec<_>>();
+    predator_entities.sort();
+    if predator_entities.len() < config.num_predators {
+        for i in predator_entities.len()..config.num_predators {
+            spawn_predator(&mut commands, &mut rng, &config, &assets, i);
+        }
+    } else {
+        for entity in predator_entities.drain(config.num_predators..) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    // Oldest first, new birds go on the end and the newest are removed first
+    let mut entities = boids.iter().map(|(entity, ..)| entity).collect::<Vec<_>>();
+    entities.sort();
+    if entities.len() < config.num_boids {
+        for i in entities.len()..config.num_boids {
+            spawn_boid(&mut commands, &mut rng, &config, &assets, i);
+        }
+    } else {
+        for entity in entities.drain(config.num_boids..) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    if *last_materials != assets.materials {
+        let num_flocks = assets.materials.len().max(1);
+        for (i, entity) in entities.iter().enumerate() {
+            let (_, mut flock, mut material) = boids.get_mut(*entity).unwrap();
+            flock.0 = i % num_flocks;
+            *material = assets.materials.get(flock.0).cloned().unwrap_or_default();
+        }
+        *last_materials = assets.materials.clone();
+    }
+}
+
+struct ResetSimulation;
+
+/// Despawn every bird and predator and start over from the seed, obstacles stay. Returns whether
+/// it reset, so [`reconcile_boids`] doesn't spawn on top of the new flock
+fn reset_simulation(
+    mut commands: Commands,
+    mut events: EventReader<ResetSimulation>,
+    actions: Res<InputActions>,
+    config: Res<BoidConfig>,
+    assets: Res<BoidAssets>,
+    mut rng: ResMut<BoidRng>,
+    mut stats: ResMut<BoidStats>,
+    boids: Query<Entity, Or<(With<Boid>, With<Predator>)>>,
+) -> bool {
+    let reset = events.iter().count() > 0 || actions.just_pressed(BOIDS_RESET);
+    if !reset {
+        return false;
+    }
+    stats.clear();
+    for entity in boids.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *rng = BoidRng::new(config.seed);
+    for i in 0..config.num_boids {
+        spawn_boid(&mut commands, &mut rng, &config, &assets, i);
+    }
+    for i in 0..config.num_predators {
+        spawn_predator(&mut commands, &mut rng, &config, &assets, i);
+    }
+    true
+}
+
+fn simulation_window(
+    mut commands: Commands,
+    egui_ctx: Res<EguiContext>,
+    mut reset: EventWriter<ResetSimulation>,
+    mut obstacle_editor: ResMut<ObstacleEditor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    obstacles: Query<Entity, With<Obstacle>>,
+) {
+    let mut action = ObstacleAction::None;
+    Window::new("Simulation").show(egui_ctx.ctx(), |ui| {
+        if ui.button("Reset simulation").clicked() {
+            reset.send(ResetSimulation);
+        }
+        ui.separator();
+        action = obstacles_ui(ui, &mut obstacle_editor);
+    });
+    match action {
+        ObstacleAction::FinishPolygon => {
+            finish_polygon(&mut commands, &mut meshes, &mut obstacle_editor)
+        }
+        ObstacleAction::Clear => {
+            obstacle_editor.polygon.clear();
+            for entity in obstacles.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        ObstacleAction::None => {}
+    }
 }
 
 #[allow(clippy::type_complexity)]
@@ -407,32 +818,29 @@ fn ls_adjustment(
         &mut Raycastable,
         (Without<Left>, Without<Right>, Without<Top>, With<Bottom>),
     >,
-    cam: Query<&OrthographicProjection, With<MainCamera>>,
-    _config: Res<BoidConfig>,
+    config: Res<BoidConfig>,
 ) {
-    let Raycastable::LS(left) = &mut *left.single_mut();
-    let Raycastable::LS(right) = &mut *right.single_mut();
-    let Raycastable::LS(top) = &mut *top.single_mut();
-    let Raycastable::LS(bottom) = &mut *bottom.single_mut();
-
-    let cam = cam.single();
-    *left = LineSegment {
-        start: Vec2::new(cam.left, cam.top) * cam.scale,
-        offset: Vec2::Y * -2. * cam.scale,
-    };
-
-    *right = LineSegment {
-        start: Vec2::new(cam.right, cam.top) * cam.scale,
-        offset: Vec2::Y * -2. * cam.scale,
-    };
-    *top = LineSegment {
-        start: Vec2::new(cam.left, cam.top) * cam.scale,
-        offset: Vec2::X * (cam.right - cam.left) * cam.scale,
-    };
-    *bottom = LineSegment {
-        start: Vec2::new(cam.left, cam.bottom) * cam.scale,
-        offset: Vec2::X * (cam.right - cam.left) * cam.scale,
-    };
+    if !config.is_changed() {
+        return;
+    }
+    let half = config.map_size / 2.;
+    *left.single_mut() = Raycastable::LS(LineSegment {
+        start: Vec2::new(-half.x, half.y),
+        offset: Vec2::Y * -config.map_size.y,
+    });
+
+    *right.single_mut() = Raycastable::LS(LineSegment {
+        start: Vec2::new(half.x, half.y),
+        offset: Vec2::Y * -config.map_size.y,
+    });
+    *top.single_mut() = Raycastable::LS(LineSegment {
+        start: Vec2::new(-half.x, half.y),
+        offset: Vec2::X * config.map_size.x,
+    });
+    *bottom.single_mut() = Raycastable::LS(LineSegment {
+        start: Vec2::new(-half.x, -half.y),
+        offset: Vec2::X * config.map_size.x,
+    });
 
     // info!("top: {:?}", top);
     // info!("right: {:?}", right);
@@ -440,6 +848,22 @@ fn ls_adjustment(
     // info!("bottom {:?}", bottom);
 }
 
+/// Snap the camera when the followed boid wraps to the other side instead of sweeping across
+fn follow_snap(mut cameras: Query<&mut FollowCamera>, config: Res<BoidConfig>) {
+    if !config.is_changed() {
+        return;
+    }
+    for mut follow in cameras.iter_mut() {
+        follow.snap_distance = match config.boundary {
+            BoundaryMode::Wrap => config.map_size.min_element() / 2.,
+            _ => f32::INFINITY,
+        };
+    }
+}
+
+/// The map edges, ignored unless the boundary is walls
+#[derive(Component)]
+struct Wall;
 #[derive(Component)]
 struct Left;
 #[derive(Component)]
@@ -449,9 +873,12 @@ struct Top;
 #[derive(Component)]
 struct Bottom;
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 enum Raycastable {
     LS(LineSegment),
+    Circle { center: Vec2, radius: f32 },
+    // Closed outline, the last point connects back to the first
+    Polygon(Vec<Vec2>),
 }
 
 #[derive(Clone, Copy, Debug, Default)]
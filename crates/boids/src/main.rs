@@ -1,3 +1,7 @@
 fn main() {
-    boids::run();
-}
\ No newline at end of file
+    if std::env::args().any(|arg| arg == "--3d") {
+        boids::run_3d();
+    } else {
+        boids::run();
+    }
+}
@@ -0,0 +1,327 @@
+// Obstacles placed with the mouse while the editor is open
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use bevy_egui::{egui, EguiContext};
+
+use super::{LineSegment, MainCamera, Raycastable};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObstacleTool {
+    None,
+    /// Click to place a circle of `circle_radius`
+    Circle,
+    /// Click to add points, click the first point again or press Finish to close it
+    Polygon,
+}
+
+pub struct ObstacleEditor {
+    pub tool: ObstacleTool,
+    pub circle_radius: f32,
+    /// Points of the polygon being placed
+    pub polygon: Vec<Vec2>,
+    pub material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for ObstacleEditor {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        Self {
+            tool: ObstacleTool::None,
+            circle_radius: 5.0,
+            polygon: Vec::new(),
+            material: materials.add(StandardMaterial {
+                base_color: Color::DARK_GRAY,
+                unlit: true,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Obstacle;
+
+/// Tool picker for the simulation window
+pub fn obstacles_ui(ui: &mut egui::Ui, editor: &mut ObstacleEditor) -> ObstacleAction {
+    let mut action = ObstacleAction::None;
+    ui.heading("Obstacles");
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut editor.tool, ObstacleTool::None, "None");
+        ui.radio_value(&mut editor.tool, ObstacleTool::Circle, "Circle");
+        ui.radio_value(&mut editor.tool, ObstacleTool::Polygon, "Polygon");
+    });
+    match editor.tool {
+        ObstacleTool::Circle => {
+            ui.add(egui::Slider::new(&mut editor.circle_radius, 0.5..=50.0).text("radius"));
+        }
+        ObstacleTool::Polygon => {
+            ui.label(format!("{} points", editor.polygon.len()));
+            if ui.button("Finish polygon").clicked() {
+                action = ObstacleAction::FinishPolygon;
+            }
+        }
+        ObstacleTool::None => {}
+    }
+    if ui.button("Clear obstacles").clicked() {
+        action = ObstacleAction::Clear;
+    }
+    action
+}
+
+pub enum ObstacleAction {
+    None,
+    FinishPolygon,
+    Clear,
+}
+
+/// Left click in the world with a tool selected places obstacles
+pub(super) fn place_obstacles(
+    mut commands: Commands,
+    egui_ctx: Res<EguiContext>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    mut editor: ResMut<ObstacleEditor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cam: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    if editor.tool == ObstacleTool::None
+        || !mouse.just_pressed(MouseButton::Left)
+        || egui_ctx.ctx().wants_pointer_input()
+    {
+        return;
+    }
+    let window = windows.get_primary().unwrap();
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // Cursor to world on the boids' plane
+    let (transform, proj) = cam.single();
+    let uv = cursor / Vec2::new(window.width(), window.height());
+    let point = transform.translation.truncate()
+        + Vec2::new(
+            proj.left + uv.x * (proj.right - proj.left),
+            proj.bottom + uv.y * (proj.top - proj.bottom),
+        ) * proj.scale;
+
+    match editor.tool {
+        ObstacleTool::Circle => {
+            let radius = editor.circle_radius;
+            spawn_circle(&mut commands, &mut meshes, &editor.material, point, radius);
+        }
+        ObstacleTool::Polygon => {
+            // Clicking near the first point closes the polygon
+            let close =
+                editor.polygon.len() >= 3 && editor.polygon[0].distance(point) < proj.scale * 0.05;
+            if close {
+                finish_polygon(&mut commands, &mut meshes, &mut editor);
+            } else {
+                editor.polygon.push(point);
+            }
+        }
+        ObstacleTool::None => {}
+    }
+}
+
+pub fn finish_polygon(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    editor: &mut ObstacleEditor,
+) {
+    let points = std::mem::take(&mut editor.polygon);
+    if points.len() < 3 {
+        return;
+    }
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(polygon_mesh(&points)),
+            material: editor.material.clone(),
+            transform: Transform::from_xyz(0.0, 0.0, -5.1),
+            ..Default::default()
+        })
+        .insert(Raycastable::Polygon(points))
+        .insert(Obstacle)
+        .insert(Name::new("Obstacle - Polygon"));
+}
+
+pub fn spawn_circle(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    material: &Handle<StandardMaterial>,
+    center: Vec2,
+    radius: f32,
+) {
+    let points = (0..32)
+        .map(|i| {
+            let angle = i as f32 / 32.0 * std::f32::consts::TAU;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect::<Vec<_>>();
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(polygon_mesh(&points)),
+            material: material.clone(),
+            transform: Transform::from_xyz(0.0, 0.0, -5.1),
+            ..Default::default()
+        })
+        .insert(Raycastable::Circle { center, radius })
+        .insert(Obstacle)
+        .insert(Name::new("Obstacle - Circle"));
+}
+
+/// Flat mesh of the outline in world space
+fn polygon_mesh(points: &[Vec2]) -> Mesh {
+    // Wind counter clockwise so the face points at the camera
+    let area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum();
+    let mut points = points.to_vec();
+    if area < 0.0 {
+        points.reverse();
+    }
+
+    let positions = points.iter().map(|p| [p.x, p.y, 0.0]).collect::<Vec<_>>();
+    let normals = vec![[0.0, 0.0, 1.0]; points.len()];
+    let uvs = vec![[0.0, 0.0]; points.len()];
+    let indices = triangulate(&points);
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+/// Ear clipping, so concave outlines work too. `points` must wind counter clockwise, whatever
+/// is left of a self intersecting outline gets a triangle fan
+fn triangulate(points: &[Vec2]) -> Vec<u32> {
+    let mut remaining = (0..points.len() as u32).collect::<Vec<_>>();
+    let mut indices = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        // A convex corner with no other point inside can be cut off
+        let ear = (0..n).find(|&i| {
+            let triangle = corner(i);
+            let [a, b, c] = triangle.map(|j| points[j as usize]);
+            (b - a).perp_dot(c - b) > 0.0
+                && !remaining
+                    .iter()
+                    .filter(|j| !triangle.contains(j))
+                    .any(|&j| in_triangle(points[j as usize], a, b, c))
+        });
+        match ear {
+            Some(i) => {
+                indices.extend(corner(i));
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+    indices.extend(
+        (1..remaining.len().saturating_sub(1))
+            .flat_map(|i| [remaining[0], remaining[i], remaining[i + 1]]),
+    );
+    indices
+}
+
+/// Inside or on the edge of the counter clockwise triangle
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(p - a) >= 0.0
+        && (c - b).perp_dot(p - b) >= 0.0
+        && (a - c).perp_dot(p - c) >= 0.0
+}
+
+/// Does the segment from `start` along `offset` touch the circle
+pub(super) fn segment_circle_intersection(segment: LineSegment, center: Vec2, radius: f32) -> bool {
+    let length_squared = segment.offset.length_squared();
+    let t = if length_squared > 0.0 {
+        ((center - segment.start).dot(segment.offset) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest = segment.start + segment.offset * t;
+    closest.distance_squared(center) <= radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(points: &[Vec2]) -> f32 {
+        points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.perp_dot(*b))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    /// Every triangle faces the same way and together they cover the outline exactly once
+    fn assert_covers(points: &[Vec2]) {
+        let indices = triangulate(points);
+        assert_eq!(indices.len(), (points.len() - 2) * 3);
+        let mut total = 0.0;
+        for triangle in indices.chunks(3) {
+            let triangle = triangle
+                .iter()
+                .map(|&i| points[i as usize])
+                .collect::<Vec<_>>();
+            let triangle_area = area(&triangle);
+            assert!(triangle_area >= 0.0, "{:?} is wound clockwise", triangle);
+            total += triangle_area;
+        }
+        assert!(
+            (total - area(points)).abs() < 1e-3,
+            "{} != {}",
+            total,
+            area(points)
+        );
+    }
+
+    #[test]
+    fn convex_outline() {
+        assert_covers(&[
+            Vec2::new(0.0, 0.0),
+            Vec2::new(4.0, 0.0),
+            Vec2::new(4.0, 3.0),
+            Vec2::new(0.0, 3.0),
+        ]);
+    }
+
+    #[test]
+    fn concave_outline() {
+        // L shape starting at the inner corner, a fan from there would leave the outline
+        assert_covers(&[
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 3.0),
+            Vec2::new(0.0, 3.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(3.0, 1.0),
+        ]);
+        // Star
+        let star = (0..10)
+            .map(|i| {
+                let angle = i as f32 / 10.0 * std::f32::consts::TAU;
+                let radius = if i % 2 == 0 { 5.0 } else { 2.0 };
+                Vec2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect::<Vec<_>>();
+        assert_covers(&star);
+    }
+}
@@ -0,0 +1,211 @@
+// Named BoidConfig tunings stored as RON assets in assets/presets
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::asset::AssetServerSettings;
+use bevy::{prelude::*, reflect::TypeUuid};
+use bevy_egui::egui::{self, ComboBox};
+use bevy_inspector_egui::{Context, Inspectable};
+use engine::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::BoidConfig;
+
+/// Shipped presets, wasm can't list the folder so these are loaded by name everywhere
+const PRESETS: &[&str] = &[
+    "presets/tight_schools.boids.ron",
+    "presets/starlings.boids.ron",
+    "presets/chaos.boids.ron",
+];
+
+pub struct PresetsPlugin;
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonAssetPlugin::<BoidPreset>::new(&["boids.ron"]))
+            .init_resource::<BoidPresets>()
+            .add_system(apply_picked_preset);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_system(save_picked_preset);
+    }
+}
+
+/// Saved with the whole config, picking one only applies the tuning, see [`BoidPresets::apply`]
+#[derive(Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6b1f0a3e-92c4-4d7e-8a55-3c0e9d4f2b71"]
+pub(super) struct BoidPreset {
+    pub name: String,
+    pub config: BoidConfig,
+}
+
+pub(super) struct BoidPresets {
+    pub handles: Vec<Handle<BoidPreset>>,
+}
+
+impl FromWorld for BoidPresets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut handles = PRESETS
+            .iter()
+            .map(|path| asset_server.load(*path))
+            .collect::<Vec<_>>();
+
+        // Presets saved from the editor only get picked up where the folder can be listed
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(folder) = asset_server.load_folder("presets") {
+            for handle in folder {
+                let handle = handle.typed::<BoidPreset>();
+                if !handles.contains(&handle) {
+                    handles.push(handle);
+                }
+            }
+        }
+
+        Self { handles }
+    }
+}
+
+impl BoidPresets {
+    /// Copy the steering and force tuning of a preset into `config`, the boid and predator
+    /// counts, seed and world size stay as they are
+    pub fn apply(preset: &BoidPreset, config: &mut BoidConfig) {
+        let tuning = &preset.config;
+        config.max_velocity = tuning.max_velocity;
+        config.min_velocity = tuning.min_velocity;
+        config.max_acceleration = tuning.max_acceleration;
+        config.neighbor_radius = tuning.neighbor_radius;
+        config.field_of_vision = tuning.field_of_vision;
+        config.avoid_radius = tuning.avoid_radius;
+        config.interflock_separation_force = tuning.interflock_separation_force;
+        config.separation_force = tuning.separation_force;
+        config.align_force = tuning.align_force;
+        config.cohesion_force = tuning.cohesion_force;
+        config.target_force = tuning.target_force;
+        config.collision_avoidance_force = tuning.collision_avoidance_force;
+        config.collision_radius = tuning.collision_radius;
+        config.turn_find_step = tuning.turn_find_step;
+        config.flee_radius = tuning.flee_radius;
+        config.flee_force = tuning.flee_force;
+    }
+}
+
+/// Preset dropdown at the top of the [`BoidConfig`] inspector. The inspector only sees this
+/// field, so the systems below apply or save the whole config
+#[derive(Clone, Default)]
+pub(super) struct PresetPicker {
+    selected: Option<Handle<BoidPreset>>,
+    picked: bool,
+    /// Name typed in for the next save
+    new_name: String,
+    save: bool,
+}
+
+impl Inspectable for PresetPicker {
+    type Attributes = ();
+
+    fn ui(&mut self, ui: &mut egui::Ui, _: (), context: &mut Context) -> bool {
+        let world = match context.world() {
+            Some(world) => world,
+            None => return false,
+        };
+        let (presets, assets) = match (
+            world.get_resource::<BoidPresets>(),
+            world.get_resource::<Assets<BoidPreset>>(),
+        ) {
+            (Some(presets), Some(assets)) => (presets, assets),
+            _ => return false,
+        };
+
+        let selected_name = self
+            .selected
+            .as_ref()
+            .and_then(|handle| assets.get(handle))
+            .map_or("Custom", |preset| preset.name.as_str());
+        let mut changed = false;
+        ui.vertical(|ui| {
+            ComboBox::from_id_source("boid_preset")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for handle in &presets.handles {
+                        if let Some(preset) = assets.get(handle) {
+                            let selected = self.selected.as_ref() == Some(handle);
+                            if ui.selectable_label(selected, &preset.name).clicked() {
+                                self.selected = Some(handle.clone());
+                                self.picked = true;
+                                changed = true;
+                            }
+                        }
+                    }
+                });
+
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_name);
+                if ui.button("Save as preset").clicked() && !self.new_name.is_empty() {
+                    self.save = true;
+                    changed = true;
+                }
+            });
+        });
+        changed
+    }
+}
+
+fn apply_picked_preset(mut config: ResMut<BoidConfig>, assets: Res<Assets<BoidPreset>>) {
+    if !config.preset.picked {
+        return;
+    }
+    config.preset.picked = false;
+    let handle = config.preset.selected.clone();
+    if let Some(preset) = handle.as_ref().and_then(|handle| assets.get(handle)) {
+        BoidPresets::apply(preset, &mut config);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_picked_preset(
+    mut config: ResMut<BoidConfig>,
+    mut presets: ResMut<BoidPresets>,
+    mut assets: ResMut<Assets<BoidPreset>>,
+    asset_server: Res<AssetServer>,
+    settings: Option<Res<AssetServerSettings>>,
+) {
+    if !config.preset.save {
+        return;
+    }
+    config.preset.save = false;
+    let name = std::mem::take(&mut config.preset.new_name);
+    let asset_path = format!("presets/{}.boids.ron", file_stem(&name));
+    let file = asset_file_path(settings.as_deref(), &asset_path);
+    let preset = BoidPreset {
+        name,
+        config: config.clone(),
+    };
+    match save_ron_file(&file, &preset) {
+        Ok(()) => {
+            info!("saved boids preset to {}", file.display());
+            let handle = asset_server.load(asset_path.as_str());
+            if !presets.handles.contains(&handle) {
+                presets.handles.push(handle.clone());
+            }
+            // The server won't load the path again, overwriting a preset updates it here
+            assets.set_untracked(&handle, preset);
+            config.preset.selected = Some(handle);
+        }
+        Err(err) => error!("failed to save {}: {}", file.display(), err),
+    }
+}
+
+/// Lowercase with underscores, "Tight schools" saves as tight_schools
+#[cfg(not(target_arch = "wasm32"))]
+fn file_stem(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,352 @@
+// Flock metrics sampled from the simulation, plotted in the editor and exportable as CSV
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{
+        plot::{Line, Plot, Value, Values},
+        Grid, Window,
+    },
+    EguiContext,
+};
+use engine::prelude::*;
+use std::{collections::VecDeque, fmt::Write};
+
+use super::{flocking::*, Boid, BoidConfig, Flock, Velocity, TIME_STEP};
+
+pub const STATS_WINDOW: &str = "Boid Stats";
+
+/// One measurement of the whole flock
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatsSample {
+    /// Seconds of simulation
+    pub time: f32,
+    pub average_speed: f32,
+    /// Length of the average heading, 1 when every boid flies the same way, near 0 when random
+    pub polarization: f32,
+    /// Groups of boids connected by chains of neighbors within `neighbor_radius`
+    pub clusters: usize,
+    pub mean_nearest_neighbor: f32,
+    pub flock_counts: Vec<usize>,
+}
+
+/// Time series of [`StatsSample`]s, filled by [`record_stats`]
+pub struct BoidStats {
+    /// Simulation steps between samples
+    pub interval: usize,
+    /// Samples kept for the plots, the oldest are dropped first
+    pub capacity: usize,
+    pub samples: VecDeque<StatsSample>,
+    step: usize,
+}
+
+impl Default for BoidStats {
+    fn default() -> Self {
+        Self {
+            interval: 10,
+            capacity: 600,
+            samples: VecDeque::new(),
+            step: 0,
+        }
+    }
+}
+
+impl BoidStats {
+    pub fn latest(&self) -> Option<&StatsSample> {
+        self.samples.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.step = 0;
+    }
+
+    /// Every kept sample, one row each with a column per flock at the end
+    pub fn to_csv(&self) -> String {
+        let num_flocks = self
+            .samples
+            .iter()
+            .map(|sample| sample.flock_counts.len())
+            .max()
+            .unwrap_or(0);
+        let mut csv =
+            String::from("time,average_speed,polarization,clusters,mean_nearest_neighbor");
+        for flock in 0..num_flocks {
+            let _ = write!(csv, ",flock_{}", flock);
+        }
+        csv.push('\n');
+        for sample in &self.samples {
+            let _ = write!(
+                csv,
+                "{},{},{},{},{}",
+                sample.time,
+                sample.average_speed,
+                sample.polarization,
+                sample.clusters,
+                sample.mean_nearest_neighbor
+            );
+            for flock in 0..num_flocks {
+                let _ = write!(csv, ",{}", sample.flock_counts.get(flock).unwrap_or(&0));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Measure `agents`, neighbors and clusters use the grid so this scales like the flocking
+pub fn measure(agents: &[Agent], grid: &SpatialGrid, neighbor_radius: f32) -> StatsSample {
+    if agents.is_empty() {
+        return StatsSample::default();
+    }
+    let count = agents.len() as f32;
+
+    let average_speed = agents.iter().map(|a| a.velocity.length()).sum::<f32>() / count;
+    let polarization = agents
+        .iter()
+        .map(|a| a.velocity.normalize_or_zero())
+        .fold(Vec2::ZERO, |total, dir| total + dir)
+        .length()
+        / count;
+
+    let mut flock_counts = Vec::new();
+    for agent in agents {
+        if flock_counts.len() <= agent.flock {
+            flock_counts.resize(agent.flock + 1, 0);
+        }
+        flock_counts[agent.flock] += 1;
+    }
+
+    // Union find over pairs within the radius, the nearest neighbor falls out of the same loop
+    let mut parents = (0..agents.len()).collect::<Vec<_>>();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    let mut candidates = Vec::new();
+    let mut nearest_total = 0.;
+    for (i, agent) in agents.iter().enumerate() {
+        grid.candidates(agent.position, neighbor_radius, &mut candidates);
+        let mut nearest = f32::INFINITY;
+        for &j in candidates.iter().filter(|&&j| j != i) {
+            let distance = agent.position.distance(agents[j].position);
+            nearest = nearest.min(distance);
+            if distance < neighbor_radius {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a] = b;
+            }
+        }
+        // Past the radius something closer may be outside the cells around it
+        if nearest > neighbor_radius {
+            nearest = grid
+                .nearest(agents, agent.position, i)
+                .map_or(f32::INFINITY, |(_, distance)| distance);
+        }
+        if nearest.is_finite() {
+            nearest_total += nearest;
+        }
+    }
+    let clusters = (0..agents.len())
+        .filter(|&i| root(&mut parents, i) == i)
+        .count();
+
+    StatsSample {
+        time: 0.,
+        average_speed,
+        polarization,
+        clusters,
+        mean_nearest_neighbor: if agents.len() > 1 {
+            nearest_total / count
+        } else {
+            0.
+        },
+        flock_counts,
+    }
+}
+
+/// Samples the flock every `interval` steps, part of the simulation stage so it runs headless
+pub(super) fn record_stats(
+    mut stats: ResMut<BoidStats>,
+    mut grid: Local<SpatialGrid>,
+    config: Res<BoidConfig>,
+    boids: Query<(Entity, &Transform, &Velocity, &Flock), With<Boid>>,
+) {
+    stats.step += 1;
+    if stats.step % stats.interval.max(1) != 0 {
+        return;
+    }
+
+    // Spawn order, so the sample doesn't depend on query order
+    let mut boids = boids.iter().collect::<Vec<_>>();
+    boids.sort_by_key(|(entity, ..)| *entity);
+    let agents = boids
+        .iter()
+        .map(|(_, transform, velocity, flock)| Agent {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            flock: flock.0,
+        })
+        .collect::<Vec<_>>();
+    grid.rebuild(config.neighbor_radius, &agents);
+
+    let sample = StatsSample {
+        time: stats.step as f32 * TIME_STEP,
+        ..measure(&agents, &grid, config.neighbor_radius)
+    };
+    stats.samples.push_back(sample);
+    while stats.samples.len() > stats.capacity {
+        stats.samples.pop_front();
+    }
+}
+
+fn plot(ui: &mut bevy_egui::egui::Ui, id: &str, stats: &BoidStats, value: fn(&StatsSample) -> f32) {
+    let values = stats
+        .samples
+        .iter()
+        .map(|sample| Value::new(sample.time as f64, value(sample) as f64))
+        .collect::<Vec<_>>();
+    ui.label(id);
+    ui.add(
+        Plot::new(id)
+            .line(Line::new(Values::from_values(values)))
+            .height(80.0)
+            .allow_drag(false)
+            .allow_zoom(false),
+    );
+}
+
+pub fn stats_window(
+    egui_ctx: Res<EguiContext>,
+    mut windows: ResMut<EditorWindows>,
+    mut stats: ResMut<BoidStats>,
+) {
+    if !windows.is_open(STATS_WINDOW) {
+        return;
+    }
+
+    let mut open = true;
+    Window::new(STATS_WINDOW)
+        .open(&mut open)
+        .vscroll(true)
+        .show(egui_ctx.ctx(), |ui| {
+            if let Some(latest) = stats.latest() {
+                Grid::new("boid_stats").striped(true).show(ui, |ui| {
+                    ui.label("Average speed");
+                    ui.label(format!("{:.2}", latest.average_speed));
+                    ui.end_row();
+                    ui.label("Polarization");
+                    ui.label(format!("{:.3}", latest.polarization));
+                    ui.end_row();
+                    ui.label("Clusters");
+                    ui.label(format!("{}", latest.clusters));
+                    ui.end_row();
+                    ui.label("Mean nearest neighbor");
+                    ui.label(format!("{:.2}", latest.mean_nearest_neighbor));
+                    ui.end_row();
+                    for (flock, count) in latest.flock_counts.iter().enumerate() {
+                        ui.label(format!("Flock {}", flock));
+                        ui.label(format!("{}", count));
+                        ui.end_row();
+                    }
+                });
+            }
+
+            plot(ui, "Average speed", &stats, |s| s.average_speed);
+            plot(ui, "Polarization", &stats, |s| s.polarization);
+            plot(ui, "Clusters", &stats, |s| s.clusters as f32);
+            plot(ui, "Mean nearest neighbor", &stats, |s| {
+                s.mean_nearest_neighbor
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Clear").clicked() {
+                    stats.clear();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Export CSV").clicked() {
+                    match std::fs::write("boids_stats.csv", stats.to_csv()) {
+                        Ok(()) => info!("saved boids_stats.csv"),
+                        Err(err) => error!("failed to save boids_stats.csv: {}", err),
+                    }
+                }
+            });
+        });
+    windows.set_open(STATS_WINDOW, open);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(x: f32, y: f32, velocity: Vec2, flock: usize) -> Agent {
+        Agent {
+            position: Vec2::new(x, y),
+            velocity,
+            flock,
+        }
+    }
+
+    fn measure_agents(agents: &[Agent], neighbor_radius: f32) -> StatsSample {
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(neighbor_radius, agents);
+        measure(agents, &grid, neighbor_radius)
+    }
+
+    #[test]
+    fn chains_of_neighbors_are_one_cluster() {
+        let right = Vec2::X;
+        let agents = [
+            // A chain, the ends are further apart than the radius
+            agent(0., 0., right, 0),
+            agent(8., 0., right, 0),
+            agent(16., 0., right, 1),
+            // A pair
+            agent(100., 100., right, 1),
+            agent(100., 106., right, 1),
+            // A loner
+            agent(-200., 50., right, 2),
+        ];
+        let sample = measure_agents(&agents, 10.);
+        assert_eq!(sample.clusters, 3);
+        assert_eq!(sample.flock_counts, [2, 3, 1]);
+    }
+
+    #[test]
+    fn polarization_and_speed() {
+        let aligned = [
+            agent(0., 0., Vec2::new(2., 0.), 0),
+            agent(50., 0., Vec2::new(4., 0.), 0),
+        ];
+        let sample = measure_agents(&aligned, 10.);
+        assert_eq!(sample.polarization, 1.);
+        assert_eq!(sample.average_speed, 3.);
+
+        let opposed = [
+            agent(0., 0., Vec2::new(2., 0.), 0),
+            agent(50., 0., Vec2::new(-4., 0.), 0),
+        ];
+        assert_eq!(measure_agents(&opposed, 10.).polarization, 0.);
+    }
+
+    #[test]
+    fn nearest_neighbors_past_the_radius_are_found() {
+        let agents = [
+            agent(0., 0., Vec2::X, 0),
+            agent(3., 4., Vec2::X, 0),
+            // 40 from the second, far outside the cells around it
+            agent(3., 44., Vec2::X, 0),
+        ];
+        let sample = measure_agents(&agents, 10.);
+        assert_eq!(sample.mean_nearest_neighbor, (5. + 5. + 40.) / 3.);
+        assert_eq!(sample.clusters, 2);
+    }
+
+    #[test]
+    fn a_lone_boid_has_no_nearest_neighbor() {
+        let sample = measure_agents(&[agent(0., 0., Vec2::X, 0)], 10.);
+        assert_eq!(sample.mean_nearest_neighbor, 0.);
+        assert_eq!(sample.clusters, 1);
+        assert_eq!(measure_agents(&[], 10.), StatsSample::default());
+    }
+}
@@ -2,6 +2,9 @@ use bevy::prelude::*;
 use engine::prelude::*;
 use wasm_bindgen::prelude::*;
 
+const MOVE_X: &str = "move_x";
+const MOVE_Y: &str = "move_y";
+
 #[wasm_bindgen(start)]
 pub fn run() {
     App::new()
@@ -9,6 +12,20 @@ pub fn run() {
             title: "Disco".to_string(),
         })
         .insert_inspector_resource::<ClearColor>(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
+        .add_input_axis(
+            MOVE_X,
+            &[AxisBinding::Buttons {
+                negative: InputBinding::Key(KeyCode::Left),
+                positive: InputBinding::Key(KeyCode::Right),
+            }],
+        )
+        .add_input_axis(
+            MOVE_Y,
+            &[AxisBinding::Buttons {
+                negative: InputBinding::Key(KeyCode::Down),
+                positive: InputBinding::Key(KeyCode::Up),
+            }],
+        )
         .add_startup_system(setup)
         .add_system(movement)
         .add_system(animate_light_direction)
@@ -23,6 +40,7 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut camera_paths: ResMut<Assets<CameraPath>>,
 ) {
     // ground
     commands
@@ -217,14 +235,29 @@ fn setup(
         .insert(Name::new("DirectionalLight"));
 
     // camera
-    commands
+    let camera = commands
         .spawn_bundle(PerspectiveCameraBundle {
             transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
 
             ..Default::default()
         })
-        .insert(CameraController::default())
-        .insert(Name::new("Camera"));
+        .insert(CameraController::orbit(Vec3::ZERO))
+        .insert(CameraPathPlayer::new(camera_paths.add(CameraPath::default())))
+        .insert(Name::new("Camera"))
+        .id();
+
+    // second camera looking straight down, switch to it from the editor's Camera menu
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(0.0, 12.0, 0.01).looking_at(Vec3::ZERO, Vec3::Y),
+            ..Default::default()
+        })
+        .insert(CameraController {
+            enabled: false,
+            ..CameraController::orbit(Vec3::ZERO)
+        })
+        .insert(Name::new("Camera - Top"));
+    commands.insert_resource(CurrentCamera(Some(camera)));
 }
 
 fn animate_light_direction(
@@ -237,24 +270,12 @@ fn animate_light_direction(
 }
 
 fn movement(
-    input: Res<Input<KeyCode>>,
+    actions: Res<InputActions>,
     time: Res<Time>,
     mut query: Query<&mut Transform, With<Movable>>,
 ) {
     for mut transform in query.iter_mut() {
-        let mut direction = Vec3::ZERO;
-        if input.pressed(KeyCode::Up) {
-            direction.y += 1.0;
-        }
-        if input.pressed(KeyCode::Down) {
-            direction.y -= 1.0;
-        }
-        if input.pressed(KeyCode::Left) {
-            direction.x -= 1.0;
-        }
-        if input.pressed(KeyCode::Right) {
-            direction.x += 1.0;
-        }
+        let direction = Vec3::new(actions.axis(MOVE_X), actions.axis(MOVE_Y), 0.0);
 
         transform.translation += time.delta_seconds() * 2.0 * direction;
     }
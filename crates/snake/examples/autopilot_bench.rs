@@ -0,0 +1,36 @@
+// Plays games headless with each autopilot strategy and reports the average length.
+// Run with: cargo run -p snake --release --example autopilot_bench -- [games] [width] [height]
+use snake::{autopilot::Strategy, headless::benchmark, rules::SnakeRules};
+use std::time::Instant;
+
+fn arg<T: std::str::FromStr>(i: usize) -> Option<T> {
+    std::env::args().nth(i).and_then(|arg| arg.parse().ok())
+}
+
+fn main() {
+    let games = arg(1).unwrap_or(1000);
+    // Same as SnakeConfig's default board_size
+    let width = arg(2).unwrap_or(32);
+    let height = arg(3).unwrap_or(18);
+
+    println!(
+        "{} games on a {}x{} board ({} cells)",
+        games,
+        width,
+        height,
+        width * height
+    );
+    for strategy in [Strategy::Greedy, Strategy::Bfs, Strategy::Hamiltonian] {
+        let start = Instant::now();
+        let result = benchmark(strategy, &SnakeRules::default(), width, height, games, 0);
+        println!(
+            "{:<12} average length {:>7.1}, best {:>4}, average ticks {:>9.1}, wins {:>5}, {:.2?}",
+            format!("{:?}", strategy),
+            result.average_length,
+            result.max_length,
+            result.average_ticks,
+            result.wins,
+            start.elapsed()
+        );
+    }
+}
@@ -0,0 +1,371 @@
+// Steers the snake instead of the arrow keys
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use std::collections::VecDeque;
+
+use super::*;
+
+#[derive(Inspectable, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Arrow keys
+    Off,
+    /// Closest move towards the nearest food that doesn't die this tick
+    Greedy,
+    /// Shortest path to food around the body, greedy when there's no path
+    Bfs,
+    /// Follows a cycle through every cell, slow but never dies once the body is on it. The cycle
+    /// ignores walls and other snakes, greedy steps around them
+    Hamiltonian,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::Off
+    }
+}
+
+#[derive(Inspectable, Default)]
+pub struct Autopilot {
+    pub strategy: Strategy,
+    #[inspectable(ignore)]
+    cycle: Option<HamiltonianCycle>,
+}
+
+impl Autopilot {
+    pub fn new(strategy: Strategy) -> Self {
+        Self {
+            strategy,
+            cycle: None,
+        }
+    }
+
+    /// Direction for the next tick
+    pub(super) fn direction(
+        &mut self,
+        grid: &Grid,
+        head: Position,
+        food: &[Position],
+        current: Direction,
+    ) -> Direction {
+        let (width, height) = (grid.width, grid.height);
+        let fallback = |strategy| match strategy {
+            Strategy::Bfs => {
+                bfs(grid, head, food).unwrap_or_else(|| greedy(grid, head, food, current))
+            }
+            _ => greedy(grid, head, food, current),
+        };
+        match self.strategy {
+            Strategy::Off => current,
+            Strategy::Greedy | Strategy::Bfs => fallback(self.strategy),
+            Strategy::Hamiltonian => {
+                if self
+                    .cycle
+                    .as_ref()
+                    .map_or(true, |cycle| cycle.width != width || cycle.height != height)
+                {
+                    self.cycle = HamiltonianCycle::new(width, height);
+                }
+                match &self.cycle {
+                    Some(cycle) => {
+                        // Only blocked right after spawning, before the body is on the cycle
+                        let direction = cycle.next(head);
+                        if grid.is_free(head.step(direction)) {
+                            direction
+                        } else {
+                            fallback(Strategy::Greedy)
+                        }
+                    }
+                    // Odd by odd boards have no cycle
+                    None => fallback(Strategy::Bfs),
+                }
+            }
+        }
+    }
+}
+
+/// Cells the head can't move into next tick. `snake_movement` checks the body before the tail
+/// moves, so the tail counts too
+pub(super) struct Grid {
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+}
+
+impl Grid {
+    pub(super) fn new(width: i32, height: i32, body: &[Position]) -> Self {
+        let mut grid = Self {
+            width,
+            height,
+            blocked: vec![false; (width.max(0) * height.max(0)) as usize],
+        };
+        for position in body {
+            grid.set_blocked(*position, true);
+        }
+        grid
+    }
+
+    pub(super) fn set_blocked(&mut self, position: Position, blocked: bool) {
+        if let Some(i) = self.index(position) {
+            self.blocked[i] = blocked;
+        }
+    }
+
+    fn index(&self, position: Position) -> Option<usize> {
+        if position.x < 0 || position.y < 0 || position.x >= self.width || position.y >= self.height
+        {
+            None
+        } else {
+            Some((position.y * self.width + position.x) as usize)
+        }
+    }
+
+    /// On the board and not blocked
+    pub(super) fn is_free(&self, position: Position) -> bool {
+        self.index(position).map_or(false, |i| !self.blocked[i])
+    }
+}
+
+fn distance(a: Position, b: Position) -> i32 {
+    (a.x - b.x).abs() + (a.y - b.y).abs()
+}
+
+fn greedy(grid: &Grid, head: Position, food: &[Position], current: Direction) -> Direction {
+    Direction::ALL
+        .iter()
+        .copied()
+        .filter(|direction| *direction != current.opposite())
+        .filter(|direction| grid.is_free(head.step(*direction)))
+        .min_by_key(|direction| {
+            let next = head.step(*direction);
+            food.iter()
+                .map(|food| distance(next, *food))
+                .min()
+                .unwrap_or(0)
+        })
+        // Boxed in, nothing saves us
+        .unwrap_or(current)
+}
+
+/// First move of the shortest path to any food
+fn bfs(grid: &Grid, head: Position, food: &[Position]) -> Option<Direction> {
+    let mut first_moves = vec![None; grid.blocked.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back((head, None));
+    while let Some((position, first_move)) = queue.pop_front() {
+        if first_move.is_some() && food.contains(&position) {
+            return first_move;
+        }
+        for direction in Direction::ALL {
+            let next = position.step(direction);
+            if let Some(i) = grid.index(next) {
+                if !grid.blocked[i] && first_moves[i].is_none() {
+                    first_moves[i] = first_move.or(Some(direction));
+                    queue.push_back((next, first_moves[i]));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Order of every cell around a cycle, needs an even width or height
+struct HamiltonianCycle {
+    width: i32,
+    height: i32,
+    next: Vec<Direction>,
+}
+
+impl HamiltonianCycle {
+    fn new(width: i32, height: i32) -> Option<Self> {
+        if width < 2 || height < 2 {
+            return None;
+        }
+        // Built for an even height, an odd height with an even width is built transposed
+        let transpose = height % 2 != 0;
+        let (w, h) = if transpose {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        if h % 2 != 0 {
+            return None;
+        }
+
+        // Right along the bottom row, zig zag up through columns 1.., then down column 0
+        let mut path = (0..w).map(|x| (x, 0)).collect::<Vec<_>>();
+        for y in 1..h {
+            if y % 2 == 1 {
+                path.extend((1..w).rev().map(|x| (x, y)));
+            } else {
+                path.extend((1..w).map(|x| (x, y)));
+            }
+        }
+        path.extend((1..h).rev().map(|y| (0, y)));
+
+        let mut next = vec![Direction::Up; (width * height) as usize];
+        for (i, &(x, y)) in path.iter().enumerate() {
+            let (nx, ny) = path[(i + 1) % path.len()];
+            let (x, y, nx, ny) = if transpose {
+                (y, x, ny, nx)
+            } else {
+                (x, y, nx, ny)
+            };
+            next[(y * width + x) as usize] = match (nx - x, ny - y) {
+                (1, 0) => Direction::Right,
+                (-1, 0) => Direction::Left,
+                (0, 1) => Direction::Up,
+                _ => Direction::Down,
+            };
+        }
+        Some(Self {
+            width,
+            height,
+            next,
+        })
+    }
+
+    fn next(&self, position: Position) -> Direction {
+        let x = position.x.clamp(0, self.width - 1);
+        let y = position.y.clamp(0, self.height - 1);
+        self.next[(y * self.width + x) as usize]
+    }
+}
+
+/// Replaces `snake_movement_input` for player 1 while a strategy is picked, runs each tick
+/// before movement and is recorded like any other turn. Other snakes are steered around like
+/// walls
+pub(super) fn autopilot_input(
+    mut autopilot: ResMut<Autopilot>,
+    mode: Res<ReplayMode>,
+    config: Res<SnakeConfig>,
+    level: Res<CurrentLevel>,
+    mut heads: Query<(&Player, &mut SnakeHead, &SnakeSegments)>,
+    positions: Query<&Position>,
+    food: Query<&Position, With<Food>>,
+) {
+    if autopilot.strategy == Strategy::Off || *mode == ReplayMode::View {
+        return;
+    }
+    let mut body = Vec::new();
+    let mut others = Vec::new();
+    for (player, _, segments) in heads.iter_mut() {
+        let segment_positions = segments
+            .0
+            .iter()
+            .filter_map(|entity| positions.get(*entity).ok().copied());
+        if player.0 == 0 {
+            body.extend(segment_positions);
+        } else {
+            others.extend(segment_positions);
+        }
+    }
+    if body.is_empty() {
+        return;
+    }
+    let mut grid = Grid::new(
+        config.board_size.x as i32,
+        config.board_size.y as i32,
+        &body,
+    );
+    for blocked in level.0.walls().chain(others) {
+        grid.set_blocked(blocked, true);
+    }
+    let food = food.iter().copied().collect::<Vec<_>>();
+    if let Some((_, mut head, _)) = heads.iter_mut().find(|(player, ..)| player.0 == 0) {
+        head.direction = autopilot.direction(&grid, body[0], &food, head.direction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    /// Follows the cycle from the bottom left until it comes back
+    fn lap(cycle: &HamiltonianCycle) -> Vec<Position> {
+        let mut position = at(0, 0);
+        let mut visited = Vec::new();
+        loop {
+            visited.push(position);
+            position = position.step(cycle.next(position));
+            if position == at(0, 0) || visited.len() > (cycle.width * cycle.height) as usize {
+                return visited;
+            }
+        }
+    }
+
+    #[test]
+    fn the_cycle_visits_every_cell_once() {
+        // 5x4 and 6x3 have an odd height and are built transposed
+        for (width, height) in [(2, 2), (4, 4), (5, 4), (6, 3), (32, 18)] {
+            let cycle = HamiltonianCycle::new(width, height).unwrap();
+            let visited = lap(&cycle);
+            assert_eq!(
+                visited.len(),
+                (width * height) as usize,
+                "{}x{}",
+                width,
+                height
+            );
+            let cells = visited.iter().collect::<HashSet<_>>();
+            assert_eq!(cells.len(), visited.len(), "{}x{}", width, height);
+            assert!(visited
+                .iter()
+                .all(|position| (0..width).contains(&position.x)
+                    && (0..height).contains(&position.y)));
+        }
+    }
+
+    #[test]
+    fn odd_by_odd_boards_have_no_cycle() {
+        assert!(HamiltonianCycle::new(3, 3).is_none());
+        assert!(HamiltonianCycle::new(5, 7).is_none());
+        assert!(HamiltonianCycle::new(1, 4).is_none());
+    }
+
+    #[test]
+    fn bfs_goes_around_the_body() {
+        // The body is between the head and the food, going up is a dead end
+        let grid = Grid::new(5, 5, &[at(1, 2), at(2, 2), at(2, 3), at(2, 4)]);
+        assert_eq!(bfs(&grid, at(1, 2), &[at(3, 2)]), Some(Direction::Down));
+    }
+
+    #[test]
+    fn bfs_takes_the_nearest_food() {
+        let grid = Grid::new(8, 1, &[at(3, 0)]);
+        assert_eq!(
+            bfs(&grid, at(3, 0), &[at(0, 0), at(7, 0)]),
+            Some(Direction::Left)
+        );
+        assert_eq!(
+            bfs(&grid, at(3, 0), &[at(0, 0), at(5, 0)]),
+            Some(Direction::Right)
+        );
+    }
+
+    #[test]
+    fn bfs_finds_nothing_out_of_reach() {
+        let grid = Grid::new(5, 1, &[at(0, 0), at(2, 0)]);
+        assert_eq!(bfs(&grid, at(0, 0), &[at(4, 0)]), None);
+    }
+
+    #[test]
+    fn hamiltonian_wins() {
+        let rules = SnakeRules::default();
+        // The transposed cycle too
+        for (width, height) in [(6, 4), (4, 5)] {
+            for seed in 0..10 {
+                let mut autopilot = Autopilot::new(Strategy::Hamiltonian);
+                let result = headless::play(&mut autopilot, &rules, width, height, seed);
+                assert!(
+                    result.won,
+                    "{}x{} seed {}: {:?}",
+                    width, height, seed, result
+                );
+            }
+        }
+    }
+}
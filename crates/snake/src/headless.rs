@@ -0,0 +1,112 @@
+// Plays games without a window using the same rules as the systems, fast enough to benchmark the
+// autopilot over thousands of games
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::VecDeque;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GameResult {
+    pub length: usize,
+    pub ticks: usize,
+    /// Filled the board
+    pub won: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BenchResult {
+    pub games: usize,
+    pub average_length: f32,
+    pub max_length: usize,
+    pub average_ticks: f32,
+    pub wins: usize,
+}
+
+/// Play one game on an open board from the same start as `spawn_snake`. Food is topped up every
+/// tick instead of every second, games where nothing is eaten for two laps of the board are
+/// stopped so loops can't hang
+pub fn play(
+    autopilot: &mut Autopilot,
+    rules: &SnakeRules,
+    width: i32,
+    height: i32,
+    seed: u64,
+) -> GameResult {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let level = Level {
+        grid: vec![".".repeat(width as usize); height as usize],
+        ..Default::default()
+    };
+    let cells = (width * height) as usize;
+    let (head, tail) = level.spawn_positions(0).unwrap();
+    let mut body = VecDeque::from(vec![head, tail]);
+    // Kept up to date as the snake moves instead of rebuilt each tick
+    let mut grid = Grid::new(width, height, body.make_contiguous());
+    let mut direction = level.direction;
+    let mut food = Vec::new();
+    let mut ticks = 0;
+    let mut hungry = 0;
+
+    loop {
+        while let Some(position) = rules.food_position(
+            &level,
+            food.len(),
+            |position| !grid.is_free(position) || food.contains(&position),
+            &mut rng,
+        ) {
+            food.push(position);
+        }
+        if food.is_empty() || hungry > cells * 2 {
+            break;
+        }
+        direction = autopilot.direction(&grid, body[0], &food, direction);
+
+        ticks += 1;
+        let head = match rules.advance(&level, body[0], direction, |position| {
+            !grid.is_free(position)
+        }) {
+            Some(head) => head,
+            None => break,
+        };
+        body.push_front(head);
+        grid.set_blocked(head, true);
+        if let Some(i) = food.iter().position(|food| *food == head) {
+            food.swap_remove(i);
+            hungry = 0;
+        } else {
+            hungry += 1;
+            let tail = body.pop_back().unwrap();
+            grid.set_blocked(tail, false);
+        }
+    }
+
+    GameResult {
+        length: body.len(),
+        ticks,
+        won: body.len() == cells,
+    }
+}
+
+/// Play `games` games with seeds `seed..seed + games`
+pub fn benchmark(
+    strategy: Strategy,
+    rules: &SnakeRules,
+    width: i32,
+    height: i32,
+    games: usize,
+    seed: u64,
+) -> BenchResult {
+    let mut autopilot = Autopilot::new(strategy);
+    let results = (0..games as u64)
+        .map(|i| play(&mut autopilot, rules, width, height, seed + i))
+        .collect::<Vec<_>>();
+
+    let games = results.len().max(1) as f32;
+    BenchResult {
+        games: results.len(),
+        average_length: results.iter().map(|r| r.length).sum::<usize>() as f32 / games,
+        max_length: results.iter().map(|r| r.length).max().unwrap_or(0),
+        average_ticks: results.iter().map(|r| r.ticks).sum::<usize>() as f32 / games,
+        wins: results.iter().filter(|r| r.won).count(),
+    }
+}
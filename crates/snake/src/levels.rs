@@ -0,0 +1,433 @@
+// Boards with walls and portals stored as RON assets in assets/levels, and the level select menu
+use bevy::{asset::LoadState, prelude::*, reflect::TypeUuid};
+use bevy_egui::{
+    egui::{Align2, Slider, Window},
+    EguiContext,
+};
+use engine::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Shipped levels, wasm can't list the folder so these are loaded by name everywhere
+const LEVELS: &[&str] = &[
+    "levels/box.level.ron",
+    "levels/pillars.level.ron",
+    "levels/portals.level.ron",
+];
+
+pub struct LevelsPlugin;
+
+impl Plugin for LevelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonAssetPlugin::<Level>::new(&["level.ron"]))
+            .init_resource::<Levels>()
+            .init_resource::<CurrentLevel>()
+            .add_system(load_level)
+            .add_system_set(
+                SystemSet::on_update(GameState::LevelSelect).with_system(level_select_window),
+            );
+    }
+}
+
+/// Positions count from the bottom left like [`Position`], `grid` rows are listed top first so
+/// the file reads like the board
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0f4a8c2e-6d1b-4b7a-9e35-8a2d7c61f0b4"]
+#[serde(default)]
+pub struct Level {
+    pub name: String,
+    /// `#` is a wall, `.` is open
+    pub grid: Vec<String>,
+    /// Moving onto either cell comes out of the other one, heading the same way
+    pub portals: Vec<((i32, i32), (i32, i32))>,
+    /// Where the head starts, the tail starts one cell behind it
+    pub spawn: (i32, i32),
+    pub direction: Direction,
+    /// Head and heading of the snakes for players 2 and up, player 1 uses `spawn`
+    pub player_spawns: Vec<((i32, i32), Direction)>,
+    /// Seconds between moves
+    pub tick: f32,
+}
+
+/// The open board the game had before levels
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            name: "Open".to_string(),
+            grid: vec![".".repeat(32); 18],
+            portals: Vec::new(),
+            spawn: (3, 3),
+            direction: Direction::Up,
+            player_spawns: vec![
+                ((28, 14), Direction::Down),
+                ((28, 3), Direction::Up),
+                ((3, 14), Direction::Down),
+            ],
+            tick: 0.150,
+        }
+    }
+}
+
+impl Level {
+    pub fn width(&self) -> i32 {
+        self.grid.first().map_or(0, |row| row.len() as i32)
+    }
+
+    pub fn height(&self) -> i32 {
+        self.grid.len() as i32
+    }
+
+    pub fn in_bounds(&self, position: Position) -> bool {
+        position.x >= 0
+            && position.y >= 0
+            && position.x < self.width()
+            && position.y < self.height()
+    }
+
+    pub fn is_wall(&self, position: Position) -> bool {
+        self.in_bounds(position)
+            && self.grid[(self.height() - 1 - position.y) as usize].as_bytes()[position.x as usize]
+                == b'#'
+    }
+
+    pub fn walls(&self) -> impl Iterator<Item = Position> + '_ {
+        let height = self.height();
+        self.grid.iter().enumerate().flat_map(move |(row, cells)| {
+            cells
+                .bytes()
+                .enumerate()
+                .filter(|(_, cell)| *cell == b'#')
+                .map(move |(x, _)| Position {
+                    x: x as i32,
+                    y: height - 1 - row as i32,
+                })
+        })
+    }
+
+    pub fn portals(&self) -> impl Iterator<Item = (Position, Position)> + '_ {
+        self.portals
+            .iter()
+            .map(|((ax, ay), (bx, by))| (Position { x: *ax, y: *ay }, Position { x: *bx, y: *by }))
+    }
+
+    /// Where the head comes out after moving onto `position`
+    pub fn portal_exit(&self, position: Position) -> Option<Position> {
+        self.portals().find_map(|(a, b)| {
+            if a == position {
+                Some(b)
+            } else if b == position {
+                Some(a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Most snakes the level has spawns for
+    pub fn max_players(&self) -> usize {
+        1 + self.player_spawns.len()
+    }
+
+    /// Head and heading of `player`'s snake, counting from 0
+    pub fn player_spawn(&self, player: usize) -> Option<(Position, Direction)> {
+        let ((x, y), direction) = if player == 0 {
+            (self.spawn, self.direction)
+        } else {
+            *self.player_spawns.get(player - 1)?
+        };
+        Some((Position { x, y }, direction))
+    }
+
+    /// Head then tail
+    pub fn spawn_positions(&self, player: usize) -> Option<(Position, Position)> {
+        self.player_spawn(player)
+            .map(|(head, direction)| (head, head.step(direction.opposite())))
+    }
+
+    /// Checks the grid is a rectangle and every snake can spawn and make its first move
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let width = self.width();
+        if width == 0 || self.height() == 0 {
+            anyhow::bail!("the grid is empty");
+        }
+        for (row, cells) in self.grid.iter().enumerate() {
+            if cells.len() as i32 != width {
+                anyhow::bail!("row {} is {} wide, expected {}", row, cells.len(), width);
+            }
+            if let Some(cell) = cells.chars().find(|cell| *cell != '#' && *cell != '.') {
+                anyhow::bail!("row {} has an unknown cell '{}'", row, cell);
+            }
+        }
+        if self.tick <= 0.0 {
+            anyhow::bail!("tick must be positive");
+        }
+
+        let open = |position: Position| self.in_bounds(position) && !self.is_wall(position);
+        let mut snakes = Vec::new();
+        for player in 0..self.max_players() {
+            let (head, direction) = self.player_spawn(player).unwrap();
+            let tail = head.step(direction.opposite());
+            let spawn = (head.x, head.y);
+            if !open(head) {
+                anyhow::bail!("spawn {:?} is blocked", spawn);
+            }
+            if !open(tail) {
+                anyhow::bail!("the tail behind spawn {:?} is blocked", spawn);
+            }
+            if !open(head.step(direction)) {
+                anyhow::bail!("the first move from spawn {:?} is blocked", spawn);
+            }
+            if snakes.contains(&head) || snakes.contains(&tail) {
+                anyhow::bail!("spawn {:?} overlaps another snake", spawn);
+            }
+            snakes.extend([head, tail]);
+        }
+
+        let mut ends = Vec::new();
+        for (a, b) in self.portals() {
+            for end in [a, b] {
+                if !open(end) {
+                    anyhow::bail!("portal {:?} is blocked", (end.x, end.y));
+                }
+                if snakes.contains(&end) {
+                    anyhow::bail!("portal {:?} is under a spawn", (end.x, end.y));
+                }
+                if ends.contains(&end) {
+                    anyhow::bail!("portal {:?} is used twice", (end.x, end.y));
+                }
+                ends.push(end);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(super) struct Levels {
+    pub handles: Vec<Handle<Level>>,
+}
+
+impl FromWorld for Levels {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut handles = LEVELS
+            .iter()
+            .map(|path| asset_server.load(*path))
+            .collect::<Vec<_>>();
+
+        // Levels added to the folder only get picked up where it can be listed
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(folder) = asset_server.load_folder("levels") {
+            for handle in folder {
+                let handle = handle.typed::<Level>();
+                if !handles.contains(&handle) {
+                    handles.push(handle);
+                }
+            }
+        }
+
+        Self { handles }
+    }
+}
+
+/// The level being played, always valid
+#[derive(Default)]
+pub(super) struct CurrentLevel(pub Level);
+
+/// Marks everything spawned for the current level's board
+#[derive(Component)]
+pub(super) struct Board;
+
+/// Rebuilds the board and refits the camera when the level changes
+fn load_level(
+    mut commands: Commands,
+    level: Res<CurrentLevel>,
+    mut config: ResMut<SnakeConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    boards: Query<Entity, With<Board>>,
+    mut cameras: Query<(&mut OrthographicProjection, &mut PanZoomCamera)>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+    let level = &level.0;
+    config.board_size = Vec2::new(level.width() as f32, level.height() as f32);
+
+    for entity in boards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let board = spawn_board(&mut commands, &mut meshes, &config);
+    let wall_mesh = meshes.add(Mesh::from(shape::Cube {
+        size: config.cell_size,
+    }));
+    let portal_mesh = meshes.add(Mesh::from(shape::Cube {
+        size: config.cell_size * config.food_scale,
+    }));
+    commands.entity(board).with_children(|builder| {
+        for position in level.walls() {
+            builder
+                .spawn_bundle(PbrBundle {
+                    mesh: wall_mesh.clone(),
+                    material: config.boarder_material.clone(),
+                    ..Default::default()
+                })
+                .insert(position)
+                .insert(Name::new("Wall"));
+        }
+        for (a, b) in level.portals() {
+            for position in [a, b] {
+                builder
+                    .spawn_bundle(PbrBundle {
+                        mesh: portal_mesh.clone(),
+                        material: config.portal_material.clone(),
+                        ..Default::default()
+                    })
+                    .insert(position)
+                    .insert(Name::new("Portal"));
+            }
+        }
+    });
+
+    for (mut projection, mut pan_zoom) in cameras.iter_mut() {
+        fit_camera(&config, &mut projection, &mut pan_zoom);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn level_select_window(
+    egui_ctx: Res<EguiContext>,
+    levels: Res<Levels>,
+    assets: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    mut current: ResMut<CurrentLevel>,
+    mut config: ResMut<SnakeConfig>,
+    mut state: ResMut<State<GameState>>,
+    saved_replays: Res<SavedReplays>,
+    mut watch_writer: EventWriter<WatchReplayEvent>,
+) {
+    let mut picked = None;
+    let mut watch = None;
+    let mut players = config.players;
+    Window::new("Levels")
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_ctx.ctx(), |ui| {
+            ui.add(Slider::new(&mut players, 1..=PLAYERS.len()).text("Players"));
+            ui.separator();
+
+            // Levels with fewer spawns than players are played with fewer snakes
+            let button_text = |level: &Level| {
+                if level.max_players() < players {
+                    format!("{} (up to {} players)", level.name, level.max_players())
+                } else {
+                    level.name.clone()
+                }
+            };
+            let open = Level::default();
+            if ui.button(button_text(&open)).clicked() {
+                picked = Some(open);
+            }
+            for handle in &levels.handles {
+                match assets.get(handle) {
+                    Some(level) => match level.validate() {
+                        Ok(()) => {
+                            if ui.button(button_text(level)).clicked() {
+                                picked = Some(level.clone());
+                            }
+                        }
+                        Err(err) => {
+                            ui.label(format!("{} is invalid: {}", level.name, err));
+                        }
+                    },
+                    None => match asset_server.get_load_state(handle) {
+                        LoadState::Failed => {
+                            let path = asset_server.get_handle_path(handle);
+                            ui.label(format!(
+                                "{} failed to load",
+                                path.map_or("A level".to_string(), |path| path
+                                    .path()
+                                    .display()
+                                    .to_string())
+                            ));
+                        }
+                        _ => {
+                            ui.label("Loading...");
+                        }
+                    },
+                }
+            }
+
+            if !saved_replays.0.is_empty() {
+                ui.separator();
+                ui.label("Replays");
+                for (name, replay) in &saved_replays.0 {
+                    if ui
+                        .button(format!("{} ({})", name, replay.level.name))
+                        .clicked()
+                    {
+                        watch = Some(replay.clone());
+                    }
+                }
+            }
+        });
+
+    // Only written when it changes so the config isn't marked changed every frame
+    if config.players != players {
+        config.players = players;
+    }
+    if let Some(level) = picked {
+        // Only replaced when it changes so the board isn't rebuilt for nothing
+        if current.0 != level {
+            current.0 = level;
+        }
+        let _ = state.set(GameState::Playing);
+    } else if let Some(replay) = watch {
+        watch_writer.send(WatchReplayEvent(replay));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(rows: &[&str]) -> Level {
+        Level {
+            grid: rows.iter().map(|row| row.to_string()).collect(),
+            spawn: (0, 1),
+            direction: Direction::Up,
+            player_spawns: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_levels() {
+        assert!(Level::default().validate().is_ok());
+        assert!(level(&["....", "....", "...."]).validate().is_ok());
+    }
+
+    #[test]
+    fn snakes_spawn_clear_of_walls() {
+        // Spawn, tail and the cell in front of the spawn
+        assert!(level(&["....", "#...", "...."]).validate().is_err());
+        assert!(level(&["....", "....", "#..."]).validate().is_err());
+        assert!(level(&["#...", "....", "...."]).validate().is_err());
+    }
+
+    #[test]
+    fn player_spawns() {
+        let two_players = Level {
+            player_spawns: vec![((3, 1), Direction::Down)],
+            ..level(&["....", "....", "...."])
+        };
+        assert!(two_players.validate().is_ok());
+        assert_eq!(two_players.max_players(), 2);
+
+        let overlapping = Level {
+            player_spawns: vec![((1, 1), Direction::Right)],
+            ..level(&["....", "....", "...."])
+        };
+        assert!(overlapping.validate().is_err());
+    }
+}
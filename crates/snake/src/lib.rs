@@ -1,19 +1,52 @@
-use bevy::{core::FixedTimestep, prelude::*};
+use bevy::{ecs::schedule::ShouldRun, prelude::*, render::camera::ScalingMode, utils::HashSet};
 use bevy_inspector_egui::Inspectable;
 use engine::prelude::*;
-use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+pub mod autopilot;
+pub mod headless;
+mod levels;
+mod players;
+pub mod replay;
+pub mod rules;
+mod score;
+
+use autopilot::*;
+pub use levels::Level;
+use levels::*;
+use players::*;
+use replay::*;
+use rules::*;
+use score::*;
+
 // TODO: track down off by one issue with left and bottom
 
 #[derive(Clone, Hash, Debug, PartialEq, Eq, SystemLabel)]
 pub enum SnakeMovement {
     Input,
+    Turn,
+    Record,
     Movement,
     Eating,
     Growth,
 }
 
+const SNAKE_LEFT: &str = "snake_left";
+const SNAKE_RIGHT: &str = "snake_right";
+const SNAKE_UP: &str = "snake_up";
+const SNAKE_DOWN: &str = "snake_down";
+
+/// Moves between food spawns, about a second at the default tick
+const FOOD_TICKS: u64 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum GameState {
+    LevelSelect,
+    Playing,
+    GameOver,
+}
+
 #[wasm_bindgen]
 pub fn run() {
     App::new()        
@@ -21,39 +54,49 @@ pub fn run() {
             title: "Snake".to_string(),
         })
         .insert_inspector_resource::<ClearColor>(ClearColor(Color::BLACK))
-        .insert_resource(SnakeSegments::default())
-        .insert_resource(LastTailPosition::default())
+        .add_plugin(PlayersPlugin)
+        .add_input_action(
+            SNAKE_RESTART,
+            &[
+                InputBinding::Key(KeyCode::Space),
+                InputBinding::Key(KeyCode::Return),
+            ],
+        )
         .init_inspector_resource::<SnakeConfig>()
+        .init_inspector_resource::<Autopilot>()
+        .init_resource::<Scores>()
+        .init_resource::<HighScores>()
+        .init_resource::<LastPlace>()
+        .init_resource::<SnakeTick>()
         .add_event::<GrowthEvent>()
         .add_event::<GameOverEvent>()
+        .add_state(GameState::LevelSelect)
+        .add_plugin(LevelsPlugin)
+        .add_plugin(ReplayPlugin)
         .add_startup_system(setup)
-        .add_startup_system(spawn_snake)
+        .add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(spawn_snake)
+                .with_system(reset_score),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(update_tick)
+                .with_system(hud),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(clear_game)
+                .with_system(record_score),
+        )
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(game_over_window))
         .add_system(
             snake_movement_input
                 .label(SnakeMovement::Input)
                 .before(SnakeMovement::Movement),
         )
-        .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(0.150))
-                .with_system(snake_movement.label(SnakeMovement::Movement))
-                .with_system(
-                    snake_eating
-                        .label(SnakeMovement::Eating)
-                        .after(SnakeMovement::Movement),
-                )
-                .with_system(
-                    snake_growth
-                        .label(SnakeMovement::Growth)
-                        .after(SnakeMovement::Eating),
-                ),
-        )
+        .add_system_set(tick_systems().with_run_criteria(snake_tick))
         .add_system(game_over.after(SnakeMovement::Movement))
-        .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(1.0))
-                .with_system(food_spawner),
-        )
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new().with_system(position_translation),
@@ -73,14 +116,17 @@ struct SnakeConfig {
     food_scale: f32,
     #[inspectable(min = Vec2::new(0.0, 0.0), max = Vec2::new(100.0, 100.0), speed = 1.0)]
     board_size: Vec2,
+    rules: SnakeRules,
+    /// Snakes on the board, picked in the level select menu
+    #[inspectable(min = 1, max = 4)]
+    players: usize,
 
     background_material: Handle<StandardMaterial>,
     boarder_material: Handle<StandardMaterial>,
     boarder_corner_material: Handle<StandardMaterial>,
-    head_material: Handle<StandardMaterial>,
-
-    segment_material: Handle<StandardMaterial>,
+    player_materials: Vec<PlayerMaterials>,
     food_material: Handle<StandardMaterial>,
+    portal_material: Handle<StandardMaterial>,
 }
 
 impl FromWorld for SnakeConfig {
@@ -92,6 +138,8 @@ impl FromWorld for SnakeConfig {
         Self {
             cell_size: 1.0,
             board_size: Vec2::new(32.0, 18.0),
+            rules: SnakeRules::default(),
+            players: 1,
             snake_head_scale: 1.0,
             snake_segment_scale: 0.8,
             food_scale: 0.65,
@@ -107,40 +155,137 @@ impl FromWorld for SnakeConfig {
                 base_color: Color::DARK_GRAY,
                 ..Default::default()
             }),
-            head_material: materials.add(StandardMaterial {
-                base_color: Color::rgb(0.7, 0.7, 0.7),
-                ..Default::default()
-            }),
-            segment_material: materials.add(StandardMaterial {
-                base_color: Color::rgb(0.3, 0.3, 0.3),
-                ..Default::default()
-            }),
+            player_materials: PlayerMaterials::all(&mut materials),
             food_material: materials.add(StandardMaterial {
                 base_color: Color::RED,
                 ..Default::default()
             }),
+            portal_material: materials.add(StandardMaterial {
+                base_color: Color::PURPLE,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+/// Runs the snake systems every `step` seconds, set from the level
+struct SnakeTick {
+    step: f32,
+    accumulator: f32,
+    /// Moves made this game, replays count ticks by moves instead of time
+    count: u64,
+}
+
+impl Default for SnakeTick {
+    fn default() -> Self {
+        Self {
+            step: 0.150,
+            accumulator: 0.0,
+            count: 0,
         }
     }
 }
 
-fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, config: Res<SnakeConfig>) {
-    // Camera
+/// Like `FixedTimestep` but the step can change while running. Ticks at most once a frame so
+/// each tick's spawns and despawns land before the next, a slow frame falls behind instead
+fn snake_tick(time: Res<Time>, mut tick: ResMut<SnakeTick>) -> ShouldRun {
+    tick.accumulator += time.delta_seconds();
+    if tick.accumulator >= tick.step {
+        tick.accumulator = (tick.accumulator - tick.step).min(tick.step);
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Systems run once a tick, in order
+fn tick_systems() -> SystemSet {
+    SystemSet::new()
+        .with_system(
+            turn_snakes
+                .label(SnakeMovement::Turn)
+                .after(SnakeMovement::Input),
+        )
+        .with_system(
+            autopilot_input
+                .after(SnakeMovement::Turn)
+                .before(SnakeMovement::Record),
+        )
+        .with_system(
+            record_turns
+                .label(SnakeMovement::Record)
+                .after(SnakeMovement::Turn),
+        )
+        .with_system(
+            snake_movement
+                .label(SnakeMovement::Movement)
+                .after(SnakeMovement::Record),
+        )
+        .with_system(
+            snake_eating
+                .label(SnakeMovement::Eating)
+                .after(SnakeMovement::Movement),
+        )
+        .with_system(
+            snake_growth
+                .label(SnakeMovement::Growth)
+                .after(SnakeMovement::Eating),
+        )
+        .with_system(update_score.after(SnakeMovement::Growth))
+        .with_system(food_spawner.after(SnakeMovement::Growth))
+}
+
+fn setup(mut commands: Commands, config: Res<SnakeConfig>) {
+    let mut projection = OrthographicProjection {
+        scaling_mode: ScalingMode::FixedVertical,
+        ..Default::default()
+    };
+    let mut pan_zoom = PanZoomCamera {
+        // arrow keys drive the snake
+        keyboard_pan: false,
+        ..Default::default()
+    };
+    fit_camera(&config, &mut projection, &mut pan_zoom);
     commands
-        .spawn_bundle(PerspectiveCameraBundle {
-            transform: Transform::from_xyz(0.0, 0., 50.),
-            ..Default::default()
+        .spawn_bundle(OrthographicCameraBundle {
+            orthographic_projection: projection,
+            // In front of the board, following only moves it across
+            transform: Transform::from_xyz(0.0, 0.0, 50.0),
+            ..OrthographicCameraBundle::new_3d()
+        })
+        .insert(pan_zoom)
+        .insert(FollowCamera {
+            damping: 0.15,
+            ..FollowCamera::top_down()
         })
-        .insert(CameraController::default())
         .insert(Name::new("Camera"));
+}
+
+/// Fit the board and its border
+fn fit_camera(
+    config: &SnakeConfig,
+    projection: &mut OrthographicProjection,
+    pan_zoom: &mut PanZoomCamera,
+) {
+    let board_half_size = config.board_size * config.cell_size * 0.5;
+    let view_half_height = board_half_size.y + config.cell_size * 2.0;
+    projection.scale = view_half_height;
+    pan_zoom.min_scale = config.cell_size * 2.0;
+    pan_zoom.max_scale = view_half_height * 2.0;
+    pan_zoom.bounds = Some((-board_half_size * 2.0, board_half_size * 2.0));
+}
 
-    // Board
+/// Background, border and lights for `board_size`, returns the parent to add level pieces to
+fn spawn_board(commands: &mut Commands, meshes: &mut Assets<Mesh>, config: &SnakeConfig) -> Entity {
     let cell_size_half = config.cell_size * 0.5;
     let board_size = config.board_size * config.cell_size;
     let board_half_size = board_size * 0.5;
+
     commands
         .spawn_bundle((
             Transform::default(),
             GlobalTransform::default(),
+            Board,
             Name::new("Board"),
         ))
         .with_children(|builder| {
@@ -263,13 +408,38 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, config: Res<S
                     ..Default::default()
                 })
                 .insert(Name::new("Light - Center"));
-        });
+        })
+        .id()
 }
 
-#[derive(Default, Component, Copy, Clone, Eq, PartialEq, Hash)]
-struct Position {
-    x: i32,
-    y: i32,
+/// Cell on the board, from the bottom left
+#[derive(Default, Component, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn step(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Left => Self {
+                x: self.x - 1,
+                ..self
+            },
+            Direction::Right => Self {
+                x: self.x + 1,
+                ..self
+            },
+            Direction::Up => Self {
+                y: self.y + 1,
+                ..self
+            },
+            Direction::Down => Self {
+                y: self.y - 1,
+                ..self
+            },
+        }
+    }
 }
 
 #[derive(Component)]
@@ -277,22 +447,28 @@ struct SnakeHead {
     direction: Direction,
 }
 
-struct GameOverEvent;
-struct GrowthEvent;
+/// Sent when the game ends, `winner` is the last snake standing in a multiplayer game
+struct GameOverEvent {
+    winner: Option<usize>,
+}
+/// The snake with this head ate
+struct GrowthEvent(Entity);
 
-#[derive(Default)]
+/// On the head, where the tail was before the last move
+#[derive(Component, Default)]
 struct LastTailPosition(Option<Position>);
 
 #[derive(Component)]
 struct SnakeSegment;
-#[derive(Default)]
+/// On the head, every segment of the snake head first
+#[derive(Component, Default)]
 struct SnakeSegments(Vec<Entity>);
 
 #[derive(Component)]
 struct Food;
 
-#[derive(PartialEq, Copy, Clone)]
-enum Direction {
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Direction {
     Left,
     Up,
     Right,
@@ -300,7 +476,9 @@ enum Direction {
 }
 
 impl Direction {
-    fn opposite(self) -> Self {
+    pub const ALL: [Direction; 4] = [Self::Left, Self::Up, Self::Right, Self::Down];
+
+    pub fn opposite(self) -> Self {
         match self {
             Self::Left => Self::Right,
             Self::Right => Self::Left,
@@ -312,40 +490,52 @@ impl Direction {
 
 fn spawn_snake(
     mut commands: Commands,
-    materials: Res<SnakeConfig>,
-    mut segments: ResMut<SnakeSegments>,
     mut meshes: ResMut<Assets<Mesh>>,
     config: Res<SnakeConfig>,
+    level: Res<CurrentLevel>,
 ) {
-    segments.0 = vec![
-        commands
-            .spawn_bundle(PbrBundle {
-                material: materials.head_material.clone(),
-                mesh: meshes.add(Mesh::from(shape::Cube {
-                    size: config.cell_size * config.snake_head_scale,
-                })),
-                ..Default::default()
-            })
-            .insert(SnakeHead {
-                direction: Direction::Up,
-            })
+    let head_mesh = meshes.add(Mesh::from(shape::Cube {
+        size: config.cell_size * config.snake_head_scale,
+    }));
+    let segment_mesh = meshes.add(Mesh::from(shape::Cube {
+        size: config.cell_size * config.snake_segment_scale,
+    }));
+    for player in 0..player_count(&config, &level.0) {
+        let (head, direction) = level.0.player_spawn(player).unwrap();
+        let materials = &config.player_materials[player];
+        let mut head_commands = commands.spawn_bundle(PbrBundle {
+            material: materials.head.clone(),
+            mesh: head_mesh.clone(),
+            ..Default::default()
+        });
+        head_commands
+            .insert(SnakeHead { direction })
             .insert(SnakeSegment)
-            .insert(Position { x: 3, y: 3 })
-            .insert(Name::new("Head"))
-            .id(),
-        spawn_segment(
-            commands,
-            meshes.add(Mesh::from(shape::Cube {
-                size: config.cell_size * config.snake_segment_scale,
-            })),
-            &materials.segment_material,
-            Position { x: 3, y: 2 },
-        ),
-    ];
+            .insert(Player(player))
+            .insert(InputQueue::default())
+            .insert(LastTailPosition::default())
+            .insert(head)
+            .insert(Name::new(format!("{} Head", PLAYERS[player].name)));
+        // The camera follows player 1
+        if player == 0 {
+            head_commands.insert(FollowTarget);
+        }
+        let head_entity = head_commands.id();
+
+        let tail_entity = spawn_segment(
+            &mut commands,
+            segment_mesh.clone(),
+            &materials.segment,
+            head.step(direction.opposite()),
+        );
+        commands
+            .entity(head_entity)
+            .insert(SnakeSegments(vec![head_entity, tail_entity]));
+    }
 }
 
 fn spawn_segment(
-    mut commands: Commands,
+    commands: &mut Commands,
     mesh: Handle<Mesh>,
     material: &Handle<StandardMaterial>,
     position: Position,
@@ -362,90 +552,117 @@ fn spawn_segment(
         .id()
 }
 
+/// Moves every snake at once, snakes that die are removed and the game ends when one is left
+/// in a multiplayer game or none in a single player one
 fn snake_movement(
-    mut last_tail_position: ResMut<LastTailPosition>,
+    mut commands: Commands,
     mut game_over_writer: EventWriter<GameOverEvent>,
-    segments: ResMut<SnakeSegments>,
     snake_resources: Res<SnakeConfig>,
-    mut heads: Query<(Entity, &SnakeHead)>,
+    level: Res<CurrentLevel>,
+    mut tick: ResMut<SnakeTick>,
+    mut snakes: Query<(
+        Entity,
+        &Player,
+        &SnakeHead,
+        &SnakeSegments,
+        &mut LastTailPosition,
+    )>,
     mut positions: Query<&mut Position>,
 ) {
-    if let Some((head_entity, head)) = heads.iter_mut().next() {
-        let segment_positions = segments
+    let mut bodies = Vec::new();
+    for (entity, _, head, segments, _) in snakes.iter_mut() {
+        let body = segments
             .0
             .iter()
-            .map(|e| *positions.get_mut(*e).unwrap())
+            .filter_map(|e| positions.get(*e).ok().copied())
             .collect::<Vec<Position>>();
-        let mut head_pos = positions.get_mut(head_entity).unwrap();
-        match &head.direction {
-            Direction::Left => {
-                head_pos.x -= 1;
-            }
-            Direction::Right => {
-                head_pos.x += 1;
-            }
-            Direction::Up => {
-                head_pos.y += 1;
+        bodies.push((entity, head.direction, body));
+    }
+    if bodies.is_empty() {
+        return;
+    }
+    tick.count += 1;
+    let moves = snake_resources.rules.advance_all(
+        &level.0,
+        &bodies
+            .iter()
+            .map(|(_, direction, body)| (body.as_slice(), *direction))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut alive = Vec::new();
+    for ((entity, _, segment_positions), next) in bodies.iter().zip(moves) {
+        let (_, player, _, segments, mut last_tail_position) = snakes.get_mut(*entity).unwrap();
+        match next {
+            Some(next) => {
+                *positions.get_mut(*entity).unwrap() = next;
+                segment_positions
+                    .iter()
+                    .zip(segments.0.iter().skip(1))
+                    .for_each(|(pos, segment)| {
+                        *positions.get_mut(*segment).unwrap() = *pos;
+                    });
+                last_tail_position.0 = Some(*segment_positions.last().unwrap());
+                alive.push(player.0);
             }
-            Direction::Down => {
-                head_pos.y -= 1;
+            None => {
+                for segment in &segments.0 {
+                    commands.entity(*segment).despawn();
+                }
             }
-        };
-        if head_pos.x < 0
-            || head_pos.y < 0
-            || head_pos.x >= snake_resources.board_size.x as i32
-            || head_pos.y >= snake_resources.board_size.y as i32
-        {
-            game_over_writer.send(GameOverEvent);
-        }
-        if segment_positions.contains(&head_pos) {
-            game_over_writer.send(GameOverEvent);
         }
-        segment_positions
-            .iter()
-            .zip(segments.0.iter().skip(1))
-            .for_each(|(pos, segment)| {
-                *positions.get_mut(*segment).unwrap() = *pos;
-            });
-        last_tail_position.0 = Some(*segment_positions.last().unwrap());
+    }
+
+    let multiplayer = bodies.len() > 1;
+    if alive.is_empty() || (multiplayer && alive.len() == 1) {
+        game_over_writer.send(GameOverEvent {
+            winner: alive.first().copied().filter(|_| multiplayer),
+        });
     }
 }
 
-fn snake_movement_input(keyboard_input: Res<Input<KeyCode>>, mut heads: Query<&mut SnakeHead>) {
-    if let Some(mut head) = heads.iter_mut().next() {
-        let dir: Direction = if keyboard_input.pressed(KeyCode::Left) {
-            Direction::Left
-        } else if keyboard_input.pressed(KeyCode::Down) {
-            Direction::Down
-        } else if keyboard_input.pressed(KeyCode::Up) {
-            Direction::Up
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            Direction::Right
-        } else {
-            head.direction
-        };
-        if dir != head.direction.opposite() {
-            head.direction = dir;
+/// Queues turns pressed this frame, `turn_snakes` takes one each tick
+fn snake_movement_input(
+    actions: Res<InputActions>,
+    autopilot: Res<Autopilot>,
+    mode: Res<ReplayMode>,
+    mut heads: Query<(&Player, &SnakeHead, &mut InputQueue)>,
+) {
+    if *mode == ReplayMode::View {
+        return;
+    }
+    for (player, head, mut queue) in heads.iter_mut() {
+        // The autopilot drives player 1
+        if player.0 == 0 && autopilot.strategy != Strategy::Off {
+            queue.0.clear();
+            continue;
+        }
+        for direction in PLAYERS[player.0].just_pressed(&actions) {
+            queue.push(head.direction, direction);
         }
     }
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Shows the game over screen, restarting spawns new snakes
 fn game_over(
-    mut commands: Commands,
     mut reader: EventReader<GameOverEvent>,
-    materials: Res<SnakeConfig>,
-    segments_res: ResMut<SnakeSegments>,
+    mut state: ResMut<State<GameState>>,
+    mut scores: ResMut<Scores>,
+) {
+    if let Some(event) = reader.iter().next() {
+        scores.winner = event.winner;
+        let _ = state.set(GameState::GameOver);
+    }
+}
+
+/// Clears the board once the last tick's spawns have landed
+fn clear_game(
+    mut commands: Commands,
     food: Query<Entity, With<Food>>,
     segments: Query<Entity, With<SnakeSegment>>,
-    meshes: ResMut<Assets<Mesh>>,
-    config: Res<SnakeConfig>,
 ) {
-    if reader.iter().next().is_some() {
-        for ent in food.iter().chain(segments.iter()) {
-            commands.entity(ent).despawn();
-        }
-        spawn_snake(commands, materials, segments_res, meshes, config);
+    for ent in food.iter().chain(segments.iter()) {
+        commands.entity(ent).despawn();
     }
 }
 
@@ -453,36 +670,41 @@ fn snake_eating(
     mut commands: Commands,
     mut growth_writer: EventWriter<GrowthEvent>,
     food_positions: Query<(Entity, &Position), With<Food>>,
-    head_positions: Query<&Position, With<SnakeHead>>,
+    head_positions: Query<(Entity, &Position), With<SnakeHead>>,
 ) {
-    for head_pos in head_positions.iter() {
+    for (head, head_pos) in head_positions.iter() {
         for (ent, food_pos) in food_positions.iter() {
             if food_pos == head_pos {
                 commands.entity(ent).despawn();
-                growth_writer.send(GrowthEvent);
+                growth_writer.send(GrowthEvent(head));
             }
         }
     }
 }
 
 fn snake_growth(
-    commands: Commands,
-    last_tail_position: Res<LastTailPosition>,
-    mut segments: ResMut<SnakeSegments>,
+    mut commands: Commands,
     mut growth_reader: EventReader<GrowthEvent>,
-    materials: Res<SnakeConfig>,
     mut meshes: ResMut<Assets<Mesh>>,
     config: Res<SnakeConfig>,
+    mut scores: ResMut<Scores>,
+    mut snakes: Query<(&Player, &mut SnakeSegments, &LastTailPosition)>,
 ) {
-    if growth_reader.iter().next().is_some() {
-        segments.0.push(spawn_segment(
-            commands,
-            meshes.add(Mesh::from(shape::Cube {
-                size: config.cell_size * config.snake_segment_scale,
-            })),
-            &materials.segment_material,
-            last_tail_position.0.unwrap(),
-        ));
+    for GrowthEvent(head) in growth_reader.iter() {
+        // Snakes that died this tick, or haven't moved yet, don't grow
+        if let Ok((player, mut segments, LastTailPosition(Some(tail)))) = snakes.get_mut(*head) {
+            if let Some(score) = scores.players.get_mut(player.0) {
+                score.food_eaten += 1;
+            }
+            segments.0.push(spawn_segment(
+                &mut commands,
+                meshes.add(Mesh::from(shape::Cube {
+                    size: config.cell_size * config.snake_segment_scale,
+                })),
+                &config.player_materials[player.0].segment,
+                *tail,
+            ));
+        }
     }
 }
 
@@ -502,11 +724,55 @@ fn position_translation(
     }
 }
 
+/// Speeds up as the longest snake grows
+fn update_tick(
+    config: Res<SnakeConfig>,
+    level: Res<CurrentLevel>,
+    snakes: Query<&SnakeSegments>,
+    mut tick: ResMut<SnakeTick>,
+) {
+    let length = snakes
+        .iter()
+        .map(|segments| segments.0.len())
+        .max()
+        .unwrap_or(0);
+    tick.step = config.rules.tick(level.0.tick, length);
+}
+
+/// Every `FOOD_TICKS` moves, placed from the game's seed so replays match
+#[allow(clippy::too_many_arguments)]
 fn food_spawner(
     mut commands: Commands,
     config: Res<SnakeConfig>,
+    level: Res<CurrentLevel>,
+    state: Res<State<GameState>>,
+    tick: Res<SnakeTick>,
+    mut rng: ResMut<GameRng>,
     mut meshes: ResMut<Assets<Mesh>>,
+    segments: Query<&Position, With<SnakeSegment>>,
+    food: Query<&Position, With<Food>>,
 ) {
+    // Nothing moved this tick without snakes
+    if state.current() != &GameState::Playing
+        || segments.iter().next().is_none()
+        || tick.count % FOOD_TICKS != 0
+    {
+        return;
+    }
+    let occupied = segments
+        .iter()
+        .chain(food.iter())
+        .copied()
+        .collect::<HashSet<_>>();
+    let position = match config.rules.food_position(
+        &level.0,
+        food.iter().count(),
+        |position| occupied.contains(&position),
+        &mut rng.0,
+    ) {
+        Some(position) => position,
+        None => return,
+    };
     commands
         .spawn_bundle(PbrBundle {
             material: config.food_material.clone(),
@@ -516,9 +782,6 @@ fn food_spawner(
             ..Default::default()
         })
         .insert(Food)
-        .insert(Position {
-            x: (random::<f32>() * config.board_size.x as f32) as i32,
-            y: (random::<f32>() * config.board_size.y as f32) as i32,
-        })
+        .insert(position)
         .insert(Name::new("Food"));
 }
@@ -0,0 +1,134 @@
+// Key bindings and colors for each snake in local multiplayer
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use engine::prelude::*;
+
+use super::*;
+
+pub(super) struct PlayerControls {
+    pub name: &'static str,
+    /// Actions for left, up, right and down
+    pub actions: [&'static str; 4],
+    pub keys: [KeyCode; 4],
+    pub head_color: Color,
+    pub segment_color: Color,
+}
+
+/// Player 1 keeps the arrow keys and the colors from before multiplayer
+pub(super) const PLAYERS: [PlayerControls; 4] = [
+    PlayerControls {
+        name: "Player 1",
+        actions: [SNAKE_LEFT, SNAKE_UP, SNAKE_RIGHT, SNAKE_DOWN],
+        keys: [KeyCode::Left, KeyCode::Up, KeyCode::Right, KeyCode::Down],
+        head_color: Color::rgb(0.7, 0.7, 0.7),
+        segment_color: Color::rgb(0.3, 0.3, 0.3),
+    },
+    PlayerControls {
+        name: "Player 2",
+        actions: [
+            "snake_2_left",
+            "snake_2_up",
+            "snake_2_right",
+            "snake_2_down",
+        ],
+        keys: [KeyCode::A, KeyCode::W, KeyCode::D, KeyCode::S],
+        head_color: Color::rgb(0.4, 0.9, 0.4),
+        segment_color: Color::rgb(0.1, 0.5, 0.1),
+    },
+    PlayerControls {
+        name: "Player 3",
+        actions: [
+            "snake_3_left",
+            "snake_3_up",
+            "snake_3_right",
+            "snake_3_down",
+        ],
+        keys: [KeyCode::J, KeyCode::I, KeyCode::L, KeyCode::K],
+        head_color: Color::rgb(0.4, 0.6, 1.0),
+        segment_color: Color::rgb(0.1, 0.25, 0.6),
+    },
+    PlayerControls {
+        name: "Player 4",
+        actions: [
+            "snake_4_left",
+            "snake_4_up",
+            "snake_4_right",
+            "snake_4_down",
+        ],
+        keys: [
+            KeyCode::Numpad4,
+            KeyCode::Numpad8,
+            KeyCode::Numpad6,
+            KeyCode::Numpad5,
+        ],
+        head_color: Color::rgb(1.0, 0.7, 0.3),
+        segment_color: Color::rgb(0.6, 0.35, 0.05),
+    },
+];
+
+impl PlayerControls {
+    /// Directions pressed this frame, queued left, down, up then right like the original
+    /// controls checked them
+    pub fn just_pressed<'a>(
+        &self,
+        actions: &'a InputActions,
+    ) -> impl Iterator<Item = Direction> + 'a {
+        let [left, up, right, down] = self.actions;
+        [
+            (left, Direction::Left),
+            (down, Direction::Down),
+            (up, Direction::Up),
+            (right, Direction::Right),
+        ]
+        .into_iter()
+        .filter(move |(action, _)| actions.just_pressed(action))
+        .map(|(_, direction)| direction)
+    }
+}
+
+pub struct PlayersPlugin;
+
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        for player in &PLAYERS {
+            for (action, key) in player.actions.iter().zip(player.keys) {
+                app.add_input_action(action, &[InputBinding::Key(key)]);
+            }
+        }
+    }
+}
+
+/// Which player a snake belongs to, counting from 0
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Player(pub usize);
+
+#[derive(Inspectable, Default)]
+pub(super) struct PlayerMaterials {
+    pub head: Handle<StandardMaterial>,
+    pub segment: Handle<StandardMaterial>,
+}
+
+impl PlayerMaterials {
+    pub fn all(materials: &mut Assets<StandardMaterial>) -> Vec<Self> {
+        PLAYERS
+            .iter()
+            .map(|player| Self {
+                head: materials.add(StandardMaterial {
+                    base_color: player.head_color,
+                    ..Default::default()
+                }),
+                segment: materials.add(StandardMaterial {
+                    base_color: player.segment_color,
+                    ..Default::default()
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Snakes in the next game, fewer than picked when the level doesn't have spawns for everyone
+pub(super) fn player_count(config: &SnakeConfig, level: &Level) -> usize {
+    config
+        .players
+        .clamp(1, level.max_players().min(PLAYERS.len()))
+}
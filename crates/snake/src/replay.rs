@@ -0,0 +1,524 @@
+// Turns queued between ticks, and replays that play a game again exactly from its seed and turns
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{Align2, Area},
+    EguiContext,
+};
+use engine::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use super::*;
+
+/// Storage key the last recorded game is saved under
+const LAST_REPLAY_KEY: &str = "last_replay";
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAY_FOLDER: &str = "replays";
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAY_EXTENSION: &str = ".replay.ron";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replay>()
+            .init_resource::<ReplayMode>()
+            .init_resource::<PendingReplay>()
+            .init_resource::<SavedReplays>()
+            .init_resource::<GameRng>()
+            .add_event::<WatchReplayEvent>()
+            .add_system(watch_replay)
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_replay))
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(replay_hud))
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(finish_replay))
+            .add_system_set(SystemSet::on_enter(GameState::LevelSelect).with_system(list_replays));
+    }
+}
+
+/// Turns waiting for the next ticks, one is taken each tick so quick turns between ticks
+/// aren't lost
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct InputQueue(pub VecDeque<Direction>);
+
+impl InputQueue {
+    /// Turns pressed past this are dropped
+    pub const MAX: usize = 3;
+
+    /// Queues a turn, `current` is the direction of the last move. Turns that don't change
+    /// direction or would reverse onto the body are dropped
+    pub fn push(&mut self, current: Direction, direction: Direction) {
+        let last = self.0.back().copied().unwrap_or(current);
+        if direction != last && direction != last.opposite() && self.0.len() < Self::MAX {
+            self.0.push_back(direction);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayInput {
+    /// Moves made before the turn, see `SnakeTick::count`
+    pub tick: u64,
+    pub player: usize,
+    pub direction: Direction,
+}
+
+/// Everything needed to play a game again, food is placed from `seed` and snakes only turn on
+/// the recorded ticks
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Replay {
+    pub seed: u64,
+    pub level: Level,
+    pub rules: SnakeRules,
+    pub players: usize,
+    /// Turns in tick order
+    pub inputs: Vec<ReplayInput>,
+    /// Moves the game lasted, 0 until it ends
+    pub ticks: u64,
+}
+
+impl Replay {
+    pub fn new(seed: u64, level: Level, rules: SnakeRules, players: usize) -> Self {
+        Self {
+            seed,
+            level,
+            rules,
+            players,
+            inputs: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    /// Direction `player` last turned, or spawned, facing
+    fn last_direction(&self, player: usize) -> Option<Direction> {
+        self.inputs
+            .iter()
+            .rev()
+            .find(|input| input.player == player)
+            .map(|input| input.direction)
+            .or_else(|| {
+                self.level
+                    .player_spawn(player)
+                    .map(|(_, direction)| direction)
+            })
+    }
+
+    /// Adds a turn when `direction` isn't where `player` was already heading
+    pub fn record(&mut self, tick: u64, player: usize, direction: Direction) {
+        if self.last_direction(player) != Some(direction) {
+            self.inputs.push(ReplayInput {
+                tick,
+                player,
+                direction,
+            });
+        }
+    }
+
+    /// Turn `player` made on `tick`
+    pub fn input(&self, tick: u64, player: usize) -> Option<Direction> {
+        let start = self.inputs.partition_point(|input| input.tick < tick);
+        self.inputs[start..]
+            .iter()
+            .take_while(|input| input.tick == tick)
+            .find(|input| input.player == player)
+            .map(|input| input.direction)
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::new(),
+        )?)
+    }
+
+    pub fn from_ron(text: &str) -> anyhow::Result<Self> {
+        Ok(ron::de::from_str(text)?)
+    }
+}
+
+/// Recording the game being played, or watching [`Replay`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ReplayMode {
+    Record,
+    View,
+}
+
+impl Default for ReplayMode {
+    fn default() -> Self {
+        ReplayMode::Record
+    }
+}
+
+/// Food placement for the current game, seeded from the replay so watching places it the same
+pub(super) struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::seed_from_u64(0))
+    }
+}
+
+/// Loads the replay's level, rules and players then starts watching it
+pub(super) struct WatchReplayEvent(pub Replay);
+
+/// Picked up by `start_replay` when the next game starts, recording when there isn't one
+#[derive(Default)]
+pub(super) struct PendingReplay(Option<Replay>);
+
+/// The last game and, on native, the files in the replay folder, read when the level select
+/// menu opens
+#[derive(Default)]
+pub(super) struct SavedReplays(pub Vec<(String, Replay)>);
+
+fn watch_replay(
+    mut events: EventReader<WatchReplayEvent>,
+    mut pending: ResMut<PendingReplay>,
+    mut current: ResMut<CurrentLevel>,
+    mut config: ResMut<SnakeConfig>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if let Some(WatchReplayEvent(replay)) = events.iter().last() {
+        if let Err(err) = replay.level.validate() {
+            error!(
+                "can't watch replay, {} is invalid: {}",
+                replay.level.name, err
+            );
+            return;
+        }
+        // Only replaced when it changes so the board isn't rebuilt for nothing
+        if current.0 != replay.level {
+            current.0 = replay.level.clone();
+        }
+        config.rules = replay.rules.clone();
+        config.players = replay.players;
+        pending.0 = Some(replay.clone());
+        let _ = state.set(GameState::Playing);
+    }
+}
+
+/// Starts recording a new seed, or watching the pending replay, and restarts the tick count
+fn start_replay(
+    config: Res<SnakeConfig>,
+    level: Res<CurrentLevel>,
+    mut pending: ResMut<PendingReplay>,
+    mut replay: ResMut<Replay>,
+    mut mode: ResMut<ReplayMode>,
+    mut rng: ResMut<GameRng>,
+    mut tick: ResMut<SnakeTick>,
+) {
+    match pending.0.take() {
+        Some(pending) => {
+            *replay = pending;
+            *mode = ReplayMode::View;
+        }
+        None => {
+            *replay = Replay::new(
+                rand::random(),
+                level.0.clone(),
+                config.rules.clone(),
+                player_count(&config, &level.0),
+            );
+            *mode = ReplayMode::Record;
+        }
+    }
+    rng.0 = StdRng::seed_from_u64(replay.seed);
+    tick.count = 0;
+    tick.accumulator = 0.0;
+}
+
+/// Takes each snake's next queued turn, or the recorded one while watching
+pub(super) fn turn_snakes(
+    mode: Res<ReplayMode>,
+    replay: Res<Replay>,
+    tick: Res<SnakeTick>,
+    mut heads: Query<(&Player, &mut SnakeHead, &mut InputQueue)>,
+) {
+    for (player, mut head, mut queue) in heads.iter_mut() {
+        let turn = match *mode {
+            ReplayMode::Record => queue.0.pop_front(),
+            ReplayMode::View => replay.input(tick.count, player.0),
+        };
+        if let Some(direction) = turn {
+            head.direction = direction;
+        }
+    }
+}
+
+/// Runs after the autopilot so its turns are recorded too
+pub(super) fn record_turns(
+    mode: Res<ReplayMode>,
+    tick: Res<SnakeTick>,
+    mut replay: ResMut<Replay>,
+    heads: Query<(&Player, &SnakeHead)>,
+) {
+    if *mode != ReplayMode::Record {
+        return;
+    }
+    for (player, head) in heads.iter() {
+        replay.record(tick.count, player.0, head.direction);
+    }
+}
+
+/// Keeps the game that just ended as the last replay
+fn finish_replay(
+    mode: Res<ReplayMode>,
+    tick: Res<SnakeTick>,
+    mut replay: ResMut<Replay>,
+    mut storage: ResMut<Storage>,
+) {
+    if *mode != ReplayMode::Record {
+        return;
+    }
+    replay.ticks = tick.count;
+    if let Err(err) = storage.set_ron(LAST_REPLAY_KEY, &*replay) {
+        error!("failed to save the last replay: {}", err);
+    }
+}
+
+fn list_replays(storage: Res<Storage>, mut saved: ResMut<SavedReplays>) {
+    saved.0.clear();
+    if let Some(last) = storage.get_ron::<Replay>(LAST_REPLAY_KEY) {
+        saved.0.push(("Last game".to_string(), last));
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    saved.0.extend(read_replay_folder());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_replay_folder() -> Vec<(String, Replay)> {
+    let entries = match std::fs::read_dir(REPLAY_FOLDER) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut replays = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = file_name.strip_suffix(REPLAY_EXTENSION)?.to_string();
+            let text = std::fs::read_to_string(entry.path()).ok()?;
+            match Replay::from_ron(&text) {
+                Ok(replay) => Some((name, replay)),
+                Err(err) => {
+                    warn!("failed to load replay {}: {}", file_name, err);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    replays.sort_by(|(a, _), (b, _)| a.cmp(b));
+    replays
+}
+
+/// Writes the replay to the replay folder, named after the time it's saved
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn save_replay(replay: &Replay) -> anyhow::Result<std::path::PathBuf> {
+    std::fs::create_dir_all(REPLAY_FOLDER)?;
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let path = std::path::Path::new(REPLAY_FOLDER).join(format!("{}{}", seconds, REPLAY_EXTENSION));
+    std::fs::write(&path, replay.to_ron()?)?;
+    Ok(path)
+}
+
+fn replay_hud(
+    egui_ctx: Res<EguiContext>,
+    mode: Res<ReplayMode>,
+    replay: Res<Replay>,
+    tick: Res<SnakeTick>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+    if *mode != ReplayMode::View {
+        return;
+    }
+    Area::new("snake_replay")
+        .anchor(Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .show(egui_ctx.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                ui.heading(format!("Replay, move {} of {}", tick.count, replay.ticks));
+                if ui.button("Stop").clicked() {
+                    game_over_writer.send(GameOverEvent { winner: None });
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The open board spawns player 1 facing up and player 2 facing down
+    fn two_players() -> Replay {
+        Replay::new(42, Level::default(), SnakeRules::default(), 2)
+    }
+
+    #[test]
+    fn quick_turns_between_ticks_are_all_kept() {
+        let mut queue = InputQueue::default();
+        queue.push(Direction::Up, Direction::Left);
+        queue.push(Direction::Up, Direction::Down);
+        assert_eq!(queue.0, [Direction::Left, Direction::Down]);
+    }
+
+    #[test]
+    fn reversing_or_keeping_the_heading_is_dropped() {
+        let mut queue = InputQueue::default();
+        queue.push(Direction::Up, Direction::Down);
+        queue.push(Direction::Up, Direction::Up);
+        assert!(queue.0.is_empty());
+    }
+
+    #[test]
+    fn the_queue_is_capped() {
+        let mut queue = InputQueue::default();
+        for direction in [
+            Direction::Left,
+            Direction::Up,
+            Direction::Left,
+            Direction::Up,
+            Direction::Left,
+        ] {
+            queue.push(Direction::Up, direction);
+        }
+        assert_eq!(queue.0.len(), InputQueue::MAX);
+    }
+
+    #[test]
+    fn only_turns_are_recorded() {
+        let mut replay = two_players();
+        replay.record(0, 0, Direction::Up);
+        replay.record(0, 1, Direction::Down);
+        assert!(replay.inputs.is_empty(), "the spawn heading isn't a turn");
+        replay.record(3, 0, Direction::Left);
+        replay.record(3, 1, Direction::Right);
+        replay.record(4, 0, Direction::Left);
+        replay.record(9, 0, Direction::Up);
+        assert_eq!(replay.inputs.len(), 3);
+    }
+
+    #[test]
+    fn turns_are_found_on_their_tick() {
+        let mut replay = two_players();
+        replay.record(3, 0, Direction::Left);
+        replay.record(3, 1, Direction::Right);
+        replay.record(9, 0, Direction::Up);
+        assert_eq!(replay.input(3, 0), Some(Direction::Left));
+        assert_eq!(replay.input(3, 1), Some(Direction::Right));
+        assert_eq!(replay.input(9, 0), Some(Direction::Up));
+        assert_eq!(replay.input(4, 0), None);
+        assert_eq!(replay.input(9, 1), None);
+    }
+
+    #[test]
+    fn a_replay_loads_back_the_same() {
+        let mut replay = two_players();
+        replay.record(3, 0, Direction::Left);
+        replay.ticks = 12;
+        let loaded = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded, replay);
+    }
+
+    #[test]
+    fn missing_fields_use_their_defaults() {
+        let replay = Replay::from_ron("(seed: 7)").unwrap();
+        assert_eq!(replay.seed, 7);
+        assert!(replay.inputs.is_empty());
+    }
+
+    /// The tick systems without rendering, each update is one tick. Watches `pending` when
+    /// there is one, otherwise records the autopilot
+    fn game(pending: Option<Replay>) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .init_resource::<SnakeConfig>()
+            .init_resource::<CurrentLevel>()
+            .insert_resource(Autopilot::new(Strategy::Bfs))
+            .init_resource::<Scores>()
+            .init_resource::<SnakeTick>()
+            .init_resource::<Replay>()
+            .init_resource::<ReplayMode>()
+            .insert_resource(PendingReplay(pending))
+            .init_resource::<GameRng>()
+            .add_event::<GrowthEvent>()
+            .add_event::<GameOverEvent>()
+            .add_state(GameState::Playing)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(spawn_snake)
+                    .with_system(reset_score)
+                    .with_system(start_replay),
+            )
+            .add_system_set(tick_systems())
+            .add_system(game_over.after(SnakeMovement::Movement));
+        app
+    }
+
+    /// Moves made, scores, every snake's body head first and the food
+    fn board(app: &mut App) -> (u64, Vec<Score>, Vec<Vec<Position>>, Vec<Position>) {
+        let world = &mut app.world;
+        let mut snakes = world
+            .query::<(&Player, &SnakeSegments)>()
+            .iter(world)
+            .map(|(player, segments)| (player.0, segments.0.clone()))
+            .collect::<Vec<_>>();
+        snakes.sort_by_key(|(player, _)| *player);
+        let bodies = snakes
+            .iter()
+            .map(|(_, segments)| {
+                segments
+                    .iter()
+                    .map(|segment| *world.get::<Position>(*segment).unwrap())
+                    .collect()
+            })
+            .collect();
+        let mut food = world
+            .query_filtered::<&Position, With<Food>>()
+            .iter(world)
+            .copied()
+            .collect::<Vec<_>>();
+        food.sort_by_key(|position| (position.x, position.y));
+        (
+            world.get_resource::<SnakeTick>().unwrap().count,
+            world.get_resource::<Scores>().unwrap().players.clone(),
+            bodies,
+            food,
+        )
+    }
+
+    fn playing(app: &App) -> bool {
+        app.world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+            == &GameState::Playing
+    }
+
+    #[test]
+    fn watching_a_recorded_game_ends_the_same() {
+        let mut recording = game(None);
+        let mut updates = 0;
+        while updates < 2000 && playing(&recording) {
+            recording.update();
+            updates += 1;
+        }
+        let replay = recording.world.get_resource::<Replay>().unwrap().clone();
+        assert!(!replay.inputs.is_empty(), "the autopilot turned");
+        let recorded = board(&mut recording);
+        assert!(recorded.1[0].food_eaten > 0, "the snake grew");
+
+        let mut watching = game(Some(replay));
+        for _ in 0..updates {
+            watching.update();
+        }
+        assert_eq!(
+            *watching.world.get_resource::<ReplayMode>().unwrap(),
+            ReplayMode::View
+        );
+        assert_eq!(board(&mut watching), recorded);
+        assert_eq!(playing(&watching), playing(&recording));
+    }
+}
@@ -0,0 +1,368 @@
+// Game rules shared by the systems and headless games, configured through `SnakeConfig::rules`
+use bevy_inspector_egui::Inspectable;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// What happens at the edge of the board
+#[derive(Inspectable, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Edges {
+    Death,
+    /// Come back in on the other side
+    Wrap,
+}
+
+impl Default for Edges {
+    fn default() -> Self {
+        Edges::Death
+    }
+}
+
+/// Who dies when two snakes' heads meet
+#[derive(Inspectable, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeadOn {
+    BothDie,
+    /// The shorter snake dies, both die when they're the same length
+    LongerSurvives,
+}
+
+impl Default for HeadOn {
+    fn default() -> Self {
+        HeadOn::BothDie
+    }
+}
+
+#[derive(Inspectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnakeRules {
+    /// Food on the board at once
+    #[inspectable(min = 1)]
+    pub max_food: usize,
+    /// Food never lands on the snake, walls or other food
+    pub food_on_free_cells: bool,
+    pub edges: Edges,
+    /// Running into yourself ends the game
+    pub self_collision: bool,
+    /// Running into another snake kills the snake that ran into it
+    pub snake_collision: bool,
+    pub head_on: HeadOn,
+    /// Seconds taken off the level's tick for each segment past the starting two
+    #[inspectable(min = 0.0, speed = 0.001)]
+    pub speedup_per_segment: f32,
+    /// Fastest tick, levels slower than this aren't sped up past it
+    #[inspectable(min = 0.01, speed = 0.001)]
+    pub min_tick: f32,
+}
+
+impl Default for SnakeRules {
+    fn default() -> Self {
+        Self {
+            max_food: 1,
+            food_on_free_cells: true,
+            edges: Edges::Death,
+            self_collision: true,
+            snake_collision: true,
+            head_on: HeadOn::BothDie,
+            speedup_per_segment: 0.002,
+            min_tick: 0.06,
+        }
+    }
+}
+
+impl SnakeRules {
+    /// Where the head ends up after moving from `head`, `None` when the move ends the game.
+    /// `occupied` is the snake before it moves
+    pub fn advance(
+        &self,
+        level: &Level,
+        head: Position,
+        direction: Direction,
+        occupied: impl Fn(Position) -> bool,
+    ) -> Option<Position> {
+        let mut next = head.step(direction);
+        if self.edges == Edges::Wrap {
+            next.x = next.x.rem_euclid(level.width());
+            next.y = next.y.rem_euclid(level.height());
+        }
+        if let Some(exit) = level.portal_exit(next) {
+            next = exit;
+        }
+        if !level.in_bounds(next) || level.is_wall(next) {
+            return None;
+        }
+        if self.self_collision && occupied(next) {
+            return None;
+        }
+        Some(next)
+    }
+
+    /// [`advance`](Self::advance) for every snake at once, `snakes` are the bodies head first
+    /// before anyone moves. Heads meeting on the same cell or swapping places are head on
+    pub fn advance_all(
+        &self,
+        level: &Level,
+        snakes: &[(&[Position], Direction)],
+    ) -> Vec<Option<Position>> {
+        let moves = snakes
+            .iter()
+            .map(|(body, direction)| {
+                self.advance(level, body[0], *direction, |position| {
+                    body.contains(&position)
+                })
+            })
+            .collect::<Vec<_>>();
+        if !self.snake_collision {
+            return moves;
+        }
+
+        let mut next = moves.clone();
+        for (i, (body, _)) in snakes.iter().enumerate() {
+            let to = match moves[i] {
+                Some(to) => to,
+                None => continue,
+            };
+            for (j, (other, _)) in snakes.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let head_on = moves[j] == Some(to) || (to == other[0] && moves[j] == Some(body[0]));
+                let dies = if head_on {
+                    match self.head_on {
+                        HeadOn::BothDie => true,
+                        HeadOn::LongerSurvives => body.len() <= other.len(),
+                    }
+                } else {
+                    other.contains(&to)
+                };
+                if dies {
+                    next[i] = None;
+                }
+            }
+        }
+        next
+    }
+
+    /// Seconds between moves for a snake `length` segments long
+    pub fn tick(&self, level_tick: f32, length: usize) -> f32 {
+        let tick = level_tick - self.speedup_per_segment * length.saturating_sub(2) as f32;
+        tick.max(self.min_tick.min(level_tick))
+    }
+
+    /// Where the next food goes, `None` with `max_food` already out or nowhere to put it.
+    /// `occupied` is the snake and the food already out
+    pub fn food_position(
+        &self,
+        level: &Level,
+        food_count: usize,
+        occupied: impl Fn(Position) -> bool,
+        rng: &mut impl Rng,
+    ) -> Option<Position> {
+        if food_count >= self.max_food {
+            return None;
+        }
+        if !self.food_on_free_cells {
+            return Some(Position {
+                x: rng.gen_range(0..level.width()),
+                y: rng.gen_range(0..level.height()),
+            });
+        }
+
+        let is_free = |position: Position| !level.is_wall(position) && !occupied(position);
+        let cells =
+            (0..level.height()).flat_map(|y| (0..level.width()).map(move |x| Position { x, y }));
+        let free = cells.clone().filter(|position| is_free(*position)).count();
+        if free == 0 {
+            return None;
+        }
+        let i = rng.gen_range(0..free);
+        cells.filter(|position| is_free(*position)).nth(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn level(rows: &[&str]) -> Level {
+        Level {
+            grid: rows.iter().map(|row| row.to_string()).collect(),
+            spawn: (0, 1),
+            direction: Direction::Up,
+            player_spawns: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    fn at(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    fn nothing(_: Position) -> bool {
+        false
+    }
+
+    #[test]
+    fn max_food_caps_the_food_on_the_board() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let rules = SnakeRules {
+            max_food: 3,
+            ..Default::default()
+        };
+        let mut food = Vec::new();
+        while let Some(position) = rules.food_position(
+            &Level::default(),
+            food.len(),
+            |p| food.contains(&p),
+            &mut rng,
+        ) {
+            food.push(position);
+            assert!(food.len() <= 3);
+        }
+        assert_eq!(food.len(), 3);
+    }
+
+    #[test]
+    fn food_on_free_cells() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let rules = SnakeRules::default();
+        // The one free cell is picked every time, walls count as taken
+        let row = level(&["#..."]);
+        let taken = [at(1, 0), at(3, 0)];
+        for _ in 0..20 {
+            assert_eq!(
+                rules.food_position(&row, 0, |p| taken.contains(&p), &mut rng),
+                Some(at(2, 0))
+            );
+        }
+        assert_eq!(rules.food_position(&row, 0, |p| p.x > 0, &mut rng), None);
+
+        let anywhere = SnakeRules {
+            food_on_free_cells: false,
+            ..rules
+        };
+        assert!(anywhere
+            .food_position(&row, 0, |_| true, &mut rng)
+            .is_some());
+    }
+
+    #[test]
+    fn edges() {
+        let open = Level::default();
+        let rules = SnakeRules::default();
+        assert_eq!(
+            rules.advance(&open, at(31, 5), Direction::Right, nothing),
+            None
+        );
+
+        let wrap = SnakeRules {
+            edges: Edges::Wrap,
+            ..rules
+        };
+        assert_eq!(
+            wrap.advance(&open, at(31, 5), Direction::Right, nothing),
+            Some(at(0, 5))
+        );
+        assert_eq!(
+            wrap.advance(&open, at(4, 0), Direction::Down, nothing),
+            Some(at(4, 17))
+        );
+        // Walls kill even when wrapping
+        let walled = level(&["....", "#..."]);
+        assert_eq!(
+            wrap.advance(&walled, at(3, 0), Direction::Right, nothing),
+            None
+        );
+    }
+
+    #[test]
+    fn portals_keep_the_heading() {
+        let portals = Level {
+            portals: vec![((5, 5), (20, 10))],
+            ..Default::default()
+        };
+        assert_eq!(
+            SnakeRules::default().advance(&portals, at(4, 5), Direction::Right, nothing),
+            Some(at(20, 10))
+        );
+    }
+
+    #[test]
+    fn self_collision() {
+        let open = Level::default();
+        let body = [at(4, 6)];
+        let rules = SnakeRules::default();
+        assert_eq!(
+            rules.advance(&open, at(4, 5), Direction::Up, |p| body.contains(&p)),
+            None
+        );
+
+        let ghost = SnakeRules {
+            self_collision: false,
+            ..rules
+        };
+        assert_eq!(
+            ghost.advance(&open, at(4, 5), Direction::Up, |p| body.contains(&p)),
+            Some(at(4, 6))
+        );
+    }
+
+    // Bodies are head first
+    const LEFT: [Position; 2] = [Position { x: 4, y: 5 }, Position { x: 3, y: 5 }];
+
+    #[test]
+    fn head_on() {
+        let open = Level::default();
+        let right = [at(6, 5), at(7, 5), at(8, 5)];
+        let meet = [(&LEFT[..], Direction::Right), (&right[..], Direction::Left)];
+        let rules = SnakeRules::default();
+        assert_eq!(rules.advance_all(&open, &meet), vec![None, None]);
+
+        let longer = SnakeRules {
+            head_on: HeadOn::LongerSurvives,
+            ..rules
+        };
+        assert_eq!(longer.advance_all(&open, &meet), vec![None, Some(at(5, 5))]);
+        // The same length swapping heads, both die
+        let next_to = [at(5, 5), at(6, 5)];
+        let swap = [
+            (&LEFT[..], Direction::Right),
+            (&next_to[..], Direction::Left),
+        ];
+        assert_eq!(longer.advance_all(&open, &swap), vec![None, None]);
+    }
+
+    #[test]
+    fn snake_collision() {
+        let open = Level::default();
+        let above = [at(5, 6), at(4, 6), at(4, 7)];
+        let cross = [(&LEFT[..], Direction::Up), (&above[..], Direction::Right)];
+        let rules = SnakeRules::default();
+        assert_eq!(rules.advance_all(&open, &cross), vec![None, Some(at(6, 6))]);
+
+        let passing = SnakeRules {
+            snake_collision: false,
+            ..rules
+        };
+        assert_eq!(
+            passing.advance_all(&open, &cross),
+            vec![Some(at(4, 6)), Some(at(6, 6))]
+        );
+    }
+
+    #[test]
+    fn speedup_per_segment() {
+        let rules = SnakeRules::default();
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        // A new snake moves at the level's tick
+        assert!(close(rules.tick(0.15, 2), 0.15));
+        assert!(close(
+            rules.tick(0.15, 12),
+            0.15 - 10.0 * rules.speedup_per_segment
+        ));
+        assert!(close(rules.tick(0.15, 1000), rules.min_tick));
+        // Slow levels aren't slowed down
+        assert!(close(rules.tick(0.05, 100), 0.05));
+    }
+}
@@ -0,0 +1,339 @@
+// Scores for each player in the current game, the HUD, the game over screen and the saved high
+// score table
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{Align2, Area, Grid, Window},
+    EguiContext,
+};
+use engine::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+pub const SNAKE_RESTART: &str = "snake_restart";
+
+/// Storage key the table is saved under
+const HIGH_SCORES_KEY: &str = "high_scores";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub length: usize,
+    pub food_eaten: usize,
+    /// Seconds
+    pub time_alive: f32,
+}
+
+/// Every player's score in the current game
+#[derive(Default)]
+pub(super) struct Scores {
+    pub players: Vec<Score>,
+    /// Last snake standing when a multiplayer game ends, `None` for a draw or single player
+    pub winner: Option<usize>,
+}
+
+/// Best games, longest first, kept in [`Storage`]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    pub scores: Vec<Score>,
+}
+
+impl HighScores {
+    pub const MAX: usize = 10;
+
+    pub fn load(storage: &Storage) -> Self {
+        storage.get_ron(HIGH_SCORES_KEY).unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &mut Storage) -> anyhow::Result<()> {
+        storage.set_ron(HIGH_SCORES_KEY, self)
+    }
+
+    /// Adds the score if it makes the table, returns its place. Ties go to the faster game
+    pub fn insert(&mut self, score: Score) -> Option<usize> {
+        let place = self
+            .scores
+            .iter()
+            .position(|other| {
+                score.length > other.length
+                    || (score.length == other.length && score.time_alive < other.time_alive)
+            })
+            .unwrap_or(self.scores.len());
+        if place >= Self::MAX {
+            return None;
+        }
+        self.scores.insert(place, score);
+        self.scores.truncate(Self::MAX);
+        Some(place)
+    }
+
+    /// Adds each player's game in order, returns where each one ended up
+    pub fn insert_all(&mut self, scores: &[Score]) -> Vec<Option<usize>> {
+        let mut places = Vec::<Option<usize>>::new();
+        for score in scores {
+            let place = self.insert(*score);
+            // Earlier players' games move down a place, or off the table, behind this one
+            if let Some(place) = place {
+                for other in places.iter_mut() {
+                    *other = other
+                        .map(|other| if other >= place { other + 1 } else { other })
+                        .filter(|other| *other < Self::MAX);
+                }
+            }
+            places.push(place);
+        }
+        places
+    }
+
+    pub fn best(&self) -> Option<&Score> {
+        self.scores.first()
+    }
+}
+
+impl FromWorld for HighScores {
+    fn from_world(world: &mut World) -> Self {
+        Self::load(world.get_resource::<Storage>().unwrap())
+    }
+}
+
+/// Places in [`HighScores`] of each player's game that just ended
+#[derive(Default)]
+pub(super) struct LastPlace(Vec<Option<usize>>);
+
+pub(super) fn reset_score(
+    config: Res<SnakeConfig>,
+    level: Res<CurrentLevel>,
+    mut scores: ResMut<Scores>,
+) {
+    *scores = Scores {
+        players: vec![Score::default(); player_count(&config, &level.0)],
+        winner: None,
+    };
+}
+
+/// Runs each tick so replays score the same, only snakes still alive are updated. Food eaten is
+/// counted in `snake_growth`
+pub(super) fn update_score(
+    tick: Res<SnakeTick>,
+    snakes: Query<(&Player, &SnakeSegments)>,
+    mut scores: ResMut<Scores>,
+) {
+    for (player, segments) in snakes.iter() {
+        if let Some(score) = scores.players.get_mut(player.0) {
+            score.length = segments.0.len();
+            score.time_alive += tick.step;
+        }
+    }
+}
+
+/// Watched replays aren't scored again
+pub(super) fn record_score(
+    mode: Res<ReplayMode>,
+    scores: Res<Scores>,
+    mut high_scores: ResMut<HighScores>,
+    mut last_place: ResMut<LastPlace>,
+    mut storage: ResMut<Storage>,
+) {
+    last_place.0.clear();
+    if *mode == ReplayMode::View {
+        return;
+    }
+    last_place.0 = high_scores.insert_all(&scores.players);
+    if last_place.0.iter().any(Option::is_some) {
+        if let Err(err) = high_scores.save(&mut storage) {
+            error!("failed to save high scores: {}", err);
+        }
+    }
+}
+
+pub(super) fn hud(egui_ctx: Res<EguiContext>, scores: Res<Scores>, high_scores: Res<HighScores>) {
+    let multiplayer = scores.players.len() > 1;
+    Area::new("snake_hud")
+        .anchor(Align2::LEFT_TOP, [10.0, 10.0])
+        .show(egui_ctx.ctx(), |ui| {
+            for (player, score) in scores.players.iter().enumerate() {
+                let name = if multiplayer {
+                    format!("{}   ", PLAYERS[player].name)
+                } else {
+                    String::new()
+                };
+                ui.heading(format!(
+                    "{}Length {}   Food {}   Time {:.0}s",
+                    name, score.length, score.food_eaten, score.time_alive
+                ));
+            }
+            ui.heading(format!(
+                "Best {}",
+                high_scores.best().map_or(0, |best| best.length)
+            ));
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn game_over_window(
+    egui_ctx: Res<EguiContext>,
+    actions: Res<InputActions>,
+    scores: Res<Scores>,
+    high_scores: Res<HighScores>,
+    last_place: Res<LastPlace>,
+    replay: Res<Replay>,
+    mut watch_writer: EventWriter<WatchReplayEvent>,
+    mut state: ResMut<State<GameState>>,
+    mut saved_to: Local<Option<String>>,
+) {
+    let mut restart = actions.just_pressed(SNAKE_RESTART);
+    let mut level_select = false;
+    let mut watch = false;
+    Window::new("Game Over")
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_ctx.ctx(), |ui| {
+            let multiplayer = scores.players.len() > 1;
+            if multiplayer {
+                ui.heading(match scores.winner {
+                    Some(winner) => format!("{} wins!", PLAYERS[winner].name),
+                    None => "Draw".to_string(),
+                });
+            }
+            for (player, score) in scores.players.iter().enumerate() {
+                let name = if multiplayer {
+                    format!("{}: ", PLAYERS[player].name)
+                } else {
+                    String::new()
+                };
+                ui.label(format!(
+                    "{}Length {}, ate {} food in {:.1}s",
+                    name, score.length, score.food_eaten, score.time_alive
+                ));
+                if let Some(Some(place)) = last_place.0.get(player) {
+                    ui.label(format!("New high score, #{}!", place + 1));
+                }
+            }
+            ui.separator();
+
+            Grid::new("high_scores").striped(true).show(ui, |ui| {
+                ui.label("#");
+                ui.label("Length");
+                ui.label("Food");
+                ui.label("Time");
+                ui.end_row();
+                for (i, high_score) in high_scores.scores.iter().enumerate() {
+                    let place = if last_place.0.contains(&Some(i)) {
+                        format!("{} >", i + 1)
+                    } else {
+                        format!("{}", i + 1)
+                    };
+                    ui.label(place);
+                    ui.label(format!("{}", high_score.length));
+                    ui.label(format!("{}", high_score.food_eaten));
+                    ui.label(format!("{:.1}s", high_score.time_alive));
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                restart |= ui.button("Restart (Space)").clicked();
+                level_select = ui.button("Levels").clicked();
+                watch = ui.button("Watch Replay").clicked();
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Save Replay").clicked() {
+                    *saved_to = Some(match save_replay(&replay) {
+                        Ok(path) => format!("Saved to {}", path.display()),
+                        Err(err) => format!("Failed to save: {}", err),
+                    });
+                }
+            });
+            if let Some(saved_to) = &*saved_to {
+                ui.label(saved_to);
+            }
+        });
+
+    if restart || level_select || watch {
+        *saved_to = None;
+    }
+    // Already changing if the key and button land on the same frame
+    if restart {
+        let _ = state.set(GameState::Playing);
+    } else if level_select {
+        let _ = state.set(GameState::LevelSelect);
+    } else if watch {
+        watch_writer.send(WatchReplayEvent(replay.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(length: usize, time_alive: f32) -> Score {
+        Score {
+            length,
+            food_eaten: length.saturating_sub(2),
+            time_alive,
+        }
+    }
+
+    fn lengths(high_scores: &HighScores) -> Vec<usize> {
+        high_scores
+            .scores
+            .iter()
+            .map(|score| score.length)
+            .collect()
+    }
+
+    #[test]
+    fn longer_games_rank_first() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.insert(score(5, 10.0)), Some(0));
+        assert_eq!(high_scores.insert(score(9, 10.0)), Some(0));
+        assert_eq!(high_scores.insert(score(7, 10.0)), Some(1));
+        assert_eq!(high_scores.insert(score(2, 10.0)), Some(3));
+        assert_eq!(lengths(&high_scores), [9, 7, 5, 2]);
+        assert_eq!(high_scores.best(), Some(&score(9, 10.0)));
+    }
+
+    #[test]
+    fn ties_go_to_the_faster_game() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(score(5, 10.0));
+        assert_eq!(high_scores.insert(score(5, 8.0)), Some(0));
+        assert_eq!(high_scores.insert(score(5, 12.0)), Some(2));
+        // The same game again goes behind the one already there
+        assert_eq!(high_scores.insert(score(5, 10.0)), Some(2));
+    }
+
+    #[test]
+    fn the_table_is_capped() {
+        let mut high_scores = HighScores::default();
+        for length in 1..=HighScores::MAX {
+            high_scores.insert(score(length + 10, 1.0));
+        }
+        assert_eq!(high_scores.insert(score(3, 1.0)), None);
+        assert_eq!(high_scores.scores.len(), HighScores::MAX);
+        assert_eq!(high_scores.insert(score(100, 1.0)), Some(0));
+        assert_eq!(high_scores.scores.len(), HighScores::MAX);
+        assert_eq!(high_scores.scores.last(), Some(&score(12, 1.0)));
+    }
+
+    #[test]
+    fn later_players_push_earlier_ones_down() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(score(10, 1.0));
+        let places = high_scores.insert_all(&[score(5, 1.0), score(20, 1.0), score(8, 1.0)]);
+        assert_eq!(places, [Some(3), Some(0), Some(2)]);
+        assert_eq!(lengths(&high_scores), [20, 10, 8, 5]);
+    }
+
+    #[test]
+    fn later_players_push_earlier_ones_off_the_table() {
+        let mut high_scores = HighScores::default();
+        for length in 1..HighScores::MAX {
+            high_scores.insert(score(length + 10, 1.0));
+        }
+        let places = high_scores.insert_all(&[score(1, 1.0), score(50, 1.0), score(2, 1.0)]);
+        assert_eq!(places, [None, Some(0), None]);
+        assert_eq!(high_scores.scores.len(), HighScores::MAX);
+        assert_eq!(high_scores.best(), Some(&score(50, 1.0)));
+    }
+}
@@ -55,13 +55,16 @@ fn setup(
     .insert(Name::new("Light - Point"));
 }
 
-fn setup_camera(mut commands: Commands) {
+fn setup_camera(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands
         .spawn_bundle(PerspectiveCameraBundle {
             transform: Transform::from_xyz(0.0, 2.5, 0.0),
             ..Default::default()
         })
         .insert(CameraController::default())
+        .insert(CameraPathPlayer::new(
+            asset_server.load("flythrough.camera_path.ron"),
+        ))
         //.insert_bundle(PickingCameraBundle::default())
         .insert(Name::new("Camera"));
 }
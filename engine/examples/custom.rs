@@ -38,7 +38,7 @@ fn setup(
             transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
             ..Default::default()
         })
-        .insert(CameraController::default())
+        .insert(CameraController::orbit(Vec3::ZERO))
         .insert(Name::new("Camera"));
 
     // light
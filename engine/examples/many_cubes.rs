@@ -17,15 +17,21 @@ fn setup(
 ) {
     const WIDTH: usize = 100;
     const HEIGHT: usize = 100;
+    // One mesh and material for all of them, so they're drawn as a single instanced batch
+    let mesh = meshes.add(Mesh::from(shape::Cube { size: 1.0 }));
+    let material = materials.add(StandardMaterial {
+        base_color: Color::PINK,
+        ..Default::default()
+    });
     for x in 0..WIDTH {
         for y in 0..HEIGHT {
             // cube
-            commands.spawn_bundle(PbrBundle {
-                mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::PINK,
+            commands.spawn_bundle(InstancedBundle {
+                mesh: mesh.clone(),
+                instanced: Instanced {
+                    material: material.clone(),
                     ..Default::default()
-                }),
+                },
                 transform: Transform::from_xyz((x as f32) * 2.0, (y as f32) * 2.0, 0.0),
                 ..Default::default()
             });
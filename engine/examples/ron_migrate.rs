@@ -0,0 +1,47 @@
+// Rewrites versioned RON files in place, run with:
+// cargo run -p engine --example ron_migrate -- [--check] <files>...
+use engine::prelude::*;
+use ron::Value;
+use serde::{Deserialize, Serialize};
+
+// Version 0 had a single `speed`, version 1 renamed it to `walk_speed`, version 2 added `run_speed`
+#[derive(Serialize, Deserialize)]
+struct CameraSettings {
+    walk_speed: f32,
+    run_speed: f32,
+}
+
+fn rename_speed(value: Value) -> anyhow::Result<Value> {
+    let mut map = match value {
+        Value::Map(map) => map,
+        _ => anyhow::bail!("expected a struct"),
+    };
+    let speed = map
+        .remove(&Value::String("speed".to_string()))
+        .ok_or_else(|| anyhow::anyhow!("missing speed"))?;
+    map.insert(Value::String("walk_speed".to_string()), speed);
+    Ok(Value::Map(map))
+}
+
+fn add_run_speed(value: Value) -> anyhow::Result<Value> {
+    let mut map = match value {
+        Value::Map(map) => map,
+        _ => anyhow::bail!("expected a struct"),
+    };
+    let walk_speed = map[&Value::String("walk_speed".to_string())]
+        .clone()
+        .into_rust::<f32>()?;
+    map.insert(
+        Value::String("run_speed".to_string()),
+        Value::Number(ron::Number::new(walk_speed as f64 * 3.0)),
+    );
+    Ok(Value::Map(map))
+}
+
+fn main() -> anyhow::Result<()> {
+    let migrations = RonMigrations::new(2)
+        .add(0, rename_speed)
+        .add(1, add_run_speed);
+
+    run_migrate_cli::<CameraSettings>(&migrations)
+}
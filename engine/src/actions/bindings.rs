@@ -0,0 +1,64 @@
+use bevy::{
+    input::gamepad::{GamepadAxisType, GamepadButtonType},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// A single button like input
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+}
+
+impl fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputBinding::Key(key) => write!(f, "{:?}", key),
+            InputBinding::Mouse(button) => write!(f, "Mouse {:?}", button),
+            InputBinding::GamepadButton(button) => write!(f, "Pad {:?}", button),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MouseAxis {
+    X,
+    Y,
+}
+
+/// Something producing a value for an axis, values from every binding are summed
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding {
+    /// -1.0 while `negative` is held, 1.0 while `positive` is held
+    Buttons {
+        negative: InputBinding,
+        positive: InputBinding,
+    },
+    /// Analog stick or trigger, -1.0 to 1.0
+    GamepadAxis(GamepadAxisType),
+    /// Mouse movement this frame in pixels, y is down
+    MouseMotion(MouseAxis),
+    /// Scroll wheel this frame in lines
+    MouseWheel,
+}
+
+impl fmt::Display for AxisBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AxisBinding::Buttons { negative, positive } => write!(f, "{} / {}", negative, positive),
+            AxisBinding::GamepadAxis(axis) => write!(f, "Pad {:?}", axis),
+            AxisBinding::MouseMotion(axis) => write!(f, "Mouse {:?}", axis),
+            AxisBinding::MouseWheel => write!(f, "Mouse Wheel"),
+        }
+    }
+}
+
+/// Bindings for every named action and axis, saved as RON
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub actions: BTreeMap<String, Vec<InputBinding>>,
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+}
@@ -0,0 +1,258 @@
+mod bindings;
+mod ui;
+
+use bevy::{
+    input::{
+        gamepad::{GamepadAxis, GamepadButton},
+        mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+        InputSystem,
+    },
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+pub use bindings::*;
+use std::path::PathBuf;
+pub use ui::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::loaders::load_ron_file_or_default;
+
+/// Named actions and axes, so games ask for "snake_left" instead of reading `KeyCode::Left`
+pub struct InputActionsPlugin {
+    /// RON file bindings are loaded from and saved to, ignored on wasm
+    pub path: PathBuf,
+}
+
+impl Plugin for InputActionsPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let loaded = load_ron_file_or_default::<InputBindings>(&self.path);
+        #[cfg(target_arch = "wasm32")]
+        let loaded = InputBindings::default();
+
+        // Plugins added before this one may have declared actions already, the file still wins
+        let mut bindings = app
+            .world
+            .get_resource_or_insert_with(InputBindings::default);
+        bindings.actions.extend(loaded.actions);
+        bindings.axes.extend(loaded.axes);
+
+        app.init_resource::<DefaultInputBindings>()
+            .insert_resource(InputBindingsPath(self.path.clone()))
+            .init_resource::<InputActions>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_input_actions.after(InputSystem),
+            )
+            .add_system(ui::input_bindings_window);
+    }
+}
+
+/// Where [`InputBindings`] are saved
+pub struct InputBindingsPath(pub PathBuf);
+
+/// Bindings as declared by the crates, used to reset
+#[derive(Default)]
+pub struct DefaultInputBindings(pub InputBindings);
+
+/// Current state of every action and axis, updated each frame before `CoreStage::Update`
+#[derive(Default)]
+pub struct InputActions {
+    pressed: HashSet<String>,
+    just_pressed: HashSet<String>,
+    just_released: HashSet<String>,
+    axes: HashMap<String, f32>,
+}
+
+impl InputActions {
+    pub fn pressed(&self, action: &str) -> bool {
+        self.pressed.contains(action)
+    }
+
+    pub fn just_pressed(&self, action: &str) -> bool {
+        self.just_pressed.contains(action)
+    }
+
+    pub fn just_released(&self, action: &str) -> bool {
+        self.just_released.contains(action)
+    }
+
+    /// Value of an axis, 0.0 if nothing is bound or it isn't declared
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.axes.get(axis).copied().unwrap_or(0.0)
+    }
+}
+
+/// Declare actions and axes with their default bindings
+pub trait AppInputActions {
+    /// Declare an action, bindings loaded from file take priority over `defaults`
+    fn add_input_action(&mut self, action: &str, defaults: &[InputBinding]) -> &mut App;
+
+    /// Declare an axis, bindings loaded from file take priority over `defaults`
+    fn add_input_axis(&mut self, axis: &str, defaults: &[AxisBinding]) -> &mut App;
+}
+
+impl AppInputActions for App {
+    fn add_input_action(&mut self, action: &str, defaults: &[InputBinding]) -> &mut App {
+        // Works before InputActionsPlugin is added, it merges the saved bindings in later
+        self.init_resource::<InputBindings>()
+            .init_resource::<DefaultInputBindings>();
+        let mut bindings = self.world.get_resource_mut::<InputBindings>().unwrap();
+        bindings
+            .actions
+            .entry(action.to_string())
+            .or_insert_with(|| defaults.to_vec());
+
+        let mut default_bindings = self
+            .world
+            .get_resource_mut::<DefaultInputBindings>()
+            .unwrap();
+        default_bindings
+            .0
+            .actions
+            .insert(action.to_string(), defaults.to_vec());
+        self
+    }
+
+    fn add_input_axis(&mut self, axis: &str, defaults: &[AxisBinding]) -> &mut App {
+        // Works before InputActionsPlugin is added, it merges the saved bindings in later
+        self.init_resource::<InputBindings>()
+            .init_resource::<DefaultInputBindings>();
+        let mut bindings = self.world.get_resource_mut::<InputBindings>().unwrap();
+        bindings
+            .axes
+            .entry(axis.to_string())
+            .or_insert_with(|| defaults.to_vec());
+
+        let mut default_bindings = self
+            .world
+            .get_resource_mut::<DefaultInputBindings>()
+            .unwrap();
+        default_bindings
+            .0
+            .axes
+            .insert(axis.to_string(), defaults.to_vec());
+        self
+    }
+}
+
+/// Raw input for a frame, shared by every binding lookup
+struct RawInput<'a> {
+    keys: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    gamepads: &'a Gamepads,
+    gamepad_buttons: &'a Input<GamepadButton>,
+    gamepad_axes: &'a Axis<GamepadAxis>,
+    mouse_motion: Vec2,
+    scroll: f32,
+}
+
+impl<'a> RawInput<'a> {
+    fn pressed(&self, binding: InputBinding) -> bool {
+        match binding {
+            InputBinding::Key(key) => self.keys.pressed(key),
+            InputBinding::Mouse(button) => self.mouse.pressed(button),
+            InputBinding::GamepadButton(button) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton(*gamepad, button))
+            }),
+        }
+    }
+
+    fn axis(&self, binding: AxisBinding) -> f32 {
+        match binding {
+            AxisBinding::Buttons { negative, positive } => {
+                let mut value = 0.0;
+                if self.pressed(negative) {
+                    value -= 1.0;
+                }
+                if self.pressed(positive) {
+                    value += 1.0;
+                }
+                value
+            }
+            // Use whichever gamepad is pushed furthest
+            AxisBinding::GamepadAxis(axis) => self
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis(*gamepad, axis)))
+                .fold(0.0, |a: f32, b: f32| if b.abs() > a.abs() { b } else { a }),
+            AxisBinding::MouseMotion(MouseAxis::X) => self.mouse_motion.x,
+            AxisBinding::MouseMotion(MouseAxis::Y) => self.mouse_motion.y,
+            AxisBinding::MouseWheel => self.scroll,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_input_actions(
+    mut actions: ResMut<InputActions>,
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut mouse_events: EventReader<MouseMotion>,
+    mut wheel_events: EventReader<MouseWheel>,
+) {
+    let mut raw = RawInput {
+        keys: &keys,
+        mouse: &mouse,
+        gamepads: &gamepads,
+        gamepad_buttons: &gamepad_buttons,
+        gamepad_axes: &gamepad_axes,
+        mouse_motion: Vec2::ZERO,
+        scroll: 0.0,
+    };
+    for mouse_event in mouse_events.iter() {
+        raw.mouse_motion += mouse_event.delta;
+    }
+    for wheel_event in wheel_events.iter() {
+        raw.scroll += match wheel_event.unit {
+            MouseScrollUnit::Line => wheel_event.y,
+            MouseScrollUnit::Pixel => wheel_event.y * 0.01,
+        };
+    }
+
+    let actions = &mut *actions;
+    actions.just_pressed.clear();
+    actions.just_released.clear();
+    for (action, action_bindings) in bindings.actions.iter() {
+        let pressed = action_bindings.iter().any(|b| raw.pressed(*b));
+        let was_pressed = actions.pressed.contains(action);
+        if pressed && !was_pressed {
+            actions.pressed.insert(action.clone());
+            actions.just_pressed.insert(action.clone());
+        } else if !pressed && was_pressed {
+            actions.pressed.remove(action);
+            actions.just_released.insert(action.clone());
+        }
+    }
+
+    actions.axes.clear();
+    for (axis, axis_bindings) in bindings.axes.iter() {
+        let value = axis_bindings.iter().map(|b| raw.axis(*b)).sum();
+        actions.axes.insert(axis.clone(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_can_be_declared_before_the_plugin() {
+        let jump = vec![InputBinding::Key(KeyCode::Space)];
+        let mut app = App::new();
+        app.add_input_action("jump", &jump)
+            .add_plugin(InputActionsPlugin {
+                path: PathBuf::from("missing_input_bindings.ron"),
+            });
+
+        let bindings = app.world.get_resource::<InputBindings>().unwrap();
+        assert_eq!(bindings.actions["jump"], jump);
+        let defaults = app.world.get_resource::<DefaultInputBindings>().unwrap();
+        assert_eq!(defaults.0.actions["jump"], jump);
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::{input::gamepad::GamepadButton, prelude::*};
+use bevy_egui::{
+    egui::{self, Grid, Window},
+    EguiContext,
+};
+
+use super::{AxisBinding, DefaultInputBindings, InputBinding, InputBindings, InputBindingsPath};
+use crate::editor::Editor;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::loaders::save_ron_file;
+
+/// Binding slot waiting for the next button press
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rebinding {
+    /// Replace binding `index` of an action, or add one if out of range
+    Action(String, usize),
+    AxisNegative(String, usize),
+    AxisPositive(String, usize),
+}
+
+/// Rebinding widget, lists every action and axis, click a binding then press a button to change it
+pub fn input_bindings_ui(
+    ui: &mut egui::Ui,
+    bindings: &mut InputBindings,
+    defaults: &InputBindings,
+    rebinding: &mut Option<Rebinding>,
+) {
+    ui.heading("Actions");
+    Grid::new("input_actions").striped(true).show(ui, |ui| {
+        for (action, action_bindings) in bindings.actions.iter_mut() {
+            ui.label(action);
+            ui.horizontal(|ui| {
+                let mut remove = None;
+                for (i, binding) in action_bindings.iter().enumerate() {
+                    let target = Rebinding::Action(action.clone(), i);
+                    let response = ui.button(binding_text(rebinding, &target, binding));
+                    if response.clicked() {
+                        *rebinding = Some(target);
+                    }
+                    if response.secondary_clicked() {
+                        remove = Some(i);
+                    }
+                }
+                if let Some(i) = remove {
+                    action_bindings.remove(i);
+                }
+                let target = Rebinding::Action(action.clone(), action_bindings.len());
+                let text = if rebinding.as_ref() == Some(&target) {
+                    "..."
+                } else {
+                    "+"
+                };
+                if ui.small_button(text).clicked() {
+                    *rebinding = Some(target);
+                }
+            });
+            ui.end_row();
+        }
+    });
+
+    ui.heading("Axes");
+    Grid::new("input_axes").striped(true).show(ui, |ui| {
+        for (axis, axis_bindings) in bindings.axes.iter() {
+            ui.label(axis);
+            ui.horizontal(|ui| {
+                for (i, binding) in axis_bindings.iter().enumerate() {
+                    match binding {
+                        AxisBinding::Buttons { negative, positive } => {
+                            for (target, button) in [
+                                (Rebinding::AxisNegative(axis.clone(), i), negative),
+                                (Rebinding::AxisPositive(axis.clone(), i), positive),
+                            ] {
+                                let text = binding_text(rebinding, &target, button);
+                                if ui.button(text).clicked() {
+                                    *rebinding = Some(target);
+                                }
+                            }
+                        }
+                        // Analog bindings can only be changed in the file
+                        _ => {
+                            ui.label(binding.to_string());
+                        }
+                    }
+                }
+            });
+            ui.end_row();
+        }
+    });
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        if rebinding.is_some() && ui.button("Cancel").clicked() {
+            *rebinding = None;
+        }
+        if ui.button("Reset to defaults").clicked() {
+            *bindings = defaults.clone();
+            *rebinding = None;
+        }
+    });
+    ui.label("Right click a binding to remove it");
+}
+
+fn binding_text(
+    rebinding: &Option<Rebinding>,
+    target: &Rebinding,
+    binding: &InputBinding,
+) -> String {
+    if rebinding.as_ref() == Some(target) {
+        "press a button...".to_string()
+    } else {
+        binding.to_string()
+    }
+}
+
+/// Apply a captured button to the slot being rebound
+pub fn apply_rebinding(bindings: &mut InputBindings, rebinding: &Rebinding, input: InputBinding) {
+    match rebinding {
+        Rebinding::Action(action, i) => {
+            if let Some(action_bindings) = bindings.actions.get_mut(action) {
+                match action_bindings.get_mut(*i) {
+                    Some(binding) => *binding = input,
+                    None => action_bindings.push(input),
+                }
+            }
+        }
+        Rebinding::AxisNegative(axis, i) | Rebinding::AxisPositive(axis, i) => {
+            if let Some(AxisBinding::Buttons { negative, positive }) = bindings
+                .axes
+                .get_mut(axis)
+                .and_then(|axis_bindings| axis_bindings.get_mut(*i))
+            {
+                match rebinding {
+                    Rebinding::AxisNegative(..) => *negative = input,
+                    _ => *positive = input,
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn input_bindings_window(
+    egui_ctx: Res<EguiContext>,
+    mut editor: ResMut<Editor>,
+    mut bindings: ResMut<InputBindings>,
+    defaults: Res<DefaultInputBindings>,
+    path: Res<InputBindingsPath>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut rebinding: Local<Option<Rebinding>>,
+) {
+    if !editor.input_bindings {
+        *rebinding = None;
+        return;
+    }
+
+    // Capture the next button press, clicks on the window itself are ignored
+    if let Some(target) = rebinding.clone() {
+        let input = keys
+            .get_just_pressed()
+            .next()
+            .map(|key| InputBinding::Key(*key))
+            .or_else(|| {
+                mouse
+                    .get_just_pressed()
+                    .find(|button| **button != MouseButton::Left)
+                    .map(|button| InputBinding::Mouse(*button))
+            })
+            .or_else(|| {
+                gamepad_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| InputBinding::GamepadButton(button.1))
+            });
+        if let Some(input) = input {
+            apply_rebinding(&mut bindings, &target, input);
+            *rebinding = None;
+        }
+    }
+
+    let mut open = editor.input_bindings;
+    Window::new("Input Bindings")
+        .open(&mut open)
+        .vscroll(true)
+        .show(egui_ctx.ctx(), |ui| {
+            input_bindings_ui(ui, &mut bindings, &defaults.0, &mut rebinding);
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Save").clicked() {
+                match save_ron_file(&path.0, &*bindings) {
+                    Ok(()) => info!("saved input bindings to {}", path.0.display()),
+                    Err(err) => error!("failed to save {}: {}", path.0.display(), err),
+                }
+            }
+        });
+    editor.input_bindings = open;
+}
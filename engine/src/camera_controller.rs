@@ -1,71 +1,222 @@
 use bevy::{
     core::Time,
-    input::{mouse::MouseMotion, Input},
+    input::gamepad::{GamepadAxisType, GamepadButtonType},
     prelude::*,
+    render::primitives::Aabb,
     window::Windows,
 };
+
+use crate::{actions::*, editor::Inspector};
+
+pub const CAMERA_MOVE_X: &str = "camera_move_x";
+pub const CAMERA_MOVE_Y: &str = "camera_move_y";
+pub const CAMERA_MOVE_Z: &str = "camera_move_z";
+pub const CAMERA_RUN: &str = "camera_run";
+pub const CAMERA_LOOK: &str = "camera_look";
+pub const CAMERA_LOOK_X: &str = "camera_look_x";
+pub const CAMERA_LOOK_Y: &str = "camera_look_y";
+pub const CAMERA_STICK_LOOK_X: &str = "camera_stick_look_x";
+pub const CAMERA_STICK_LOOK_Y: &str = "camera_stick_look_y";
+pub const CAMERA_PAN: &str = "camera_pan";
+pub const CAMERA_ZOOM: &str = "camera_zoom";
+pub const CAMERA_FRAME: &str = "camera_frame";
+
 pub struct CameraControllerPlugin;
 
 impl Plugin for CameraControllerPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        use InputBinding::*;
+
+        app.add_input_axis(
+            CAMERA_MOVE_X,
+            &[
+                AxisBinding::Buttons {
+                    negative: Key(KeyCode::A),
+                    positive: Key(KeyCode::D),
+                },
+                AxisBinding::GamepadAxis(GamepadAxisType::LeftStickX),
+            ],
+        )
+        .add_input_axis(
+            CAMERA_MOVE_Y,
+            &[
+                AxisBinding::Buttons {
+                    negative: Key(KeyCode::Q),
+                    positive: Key(KeyCode::E),
+                },
+                AxisBinding::Buttons {
+                    negative: GamepadButton(GamepadButtonType::LeftTrigger2),
+                    positive: GamepadButton(GamepadButtonType::RightTrigger2),
+                },
+            ],
+        )
+        .add_input_axis(
+            CAMERA_MOVE_Z,
+            &[
+                AxisBinding::Buttons {
+                    negative: Key(KeyCode::S),
+                    positive: Key(KeyCode::W),
+                },
+                AxisBinding::GamepadAxis(GamepadAxisType::LeftStickY),
+            ],
+        )
+        .add_input_action(
+            CAMERA_RUN,
+            &[
+                Key(KeyCode::LShift),
+                GamepadButton(GamepadButtonType::LeftThumb),
+            ],
+        )
+        .add_input_action(CAMERA_LOOK, &[Mouse(MouseButton::Right)])
+        .add_input_axis(CAMERA_LOOK_X, &[AxisBinding::MouseMotion(MouseAxis::X)])
+        .add_input_axis(CAMERA_LOOK_Y, &[AxisBinding::MouseMotion(MouseAxis::Y)])
+        .add_input_axis(
+            CAMERA_STICK_LOOK_X,
+            &[AxisBinding::GamepadAxis(GamepadAxisType::RightStickX)],
+        )
+        .add_input_axis(
+            CAMERA_STICK_LOOK_Y,
+            &[AxisBinding::GamepadAxis(GamepadAxisType::RightStickY)],
+        )
+        .add_input_action(CAMERA_PAN, &[Mouse(MouseButton::Middle)])
+        .add_input_axis(CAMERA_ZOOM, &[AxisBinding::MouseWheel])
+        .add_input_action(CAMERA_FRAME, &[Key(KeyCode::F)]);
+
         app.add_system(init_camera_controller)
-            .add_system(update_camera_controller);
+            .add_system(update_camera_controller)
+            .add_system(frame_selected);
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Free flying with the camera_move axes, look while camera_look is held
+    Fly,
+    /// Turntable around `focus`, rotate with camera_look, pan with camera_pan, camera_zoom to zoom
+    Orbit,
+}
+
 #[derive(Component)]
 pub struct CameraController {
     pub enabled: bool,
+    pub mode: CameraMode,
+    /// Radians per pixel of mouse movement
     pub sensitivity: f32,
-    pub key_forward: KeyCode,
-    pub key_back: KeyCode,
-    pub key_left: KeyCode,
-    pub key_right: KeyCode,
-    pub key_up: KeyCode,
-    pub key_down: KeyCode,
-    pub key_run: KeyCode,
-    pub mouse_look: MouseButton,
     pub walk_speed: f32,
     pub run_speed: f32,
-    pub friction: f32,
+    /// Target pitch, the rotation eases toward it
     pub pitch: Option<f32>,
+    /// Target yaw, the rotation eases toward it
     pub yaw: Option<f32>,
+    /// Camera space velocity, x right, y world up, z forward
     pub velocity: Vec3,
+    /// Seconds for the velocity to get most of the way to full speed while moving, 0.0 is instant
     pub position_smoothness: f32,
+    /// Seconds for the velocity to die down after letting go, 0.0 stops instantly
+    pub damping: f32,
+    /// Seconds for the rotation to catch up with yaw and pitch, 0.0 is instant
     pub rotation_smoothness: f32,
+
+    // Gamepad
+    /// Stick values below this are ignored, the rest of the range is rescaled to 0.0 - 1.0
+    pub stick_dead_zone: f32,
+    /// Right stick turn rate at full tilt, radians per second
+    pub stick_look_speed: f32,
+    /// Look response curve, 1.0 is linear, higher gives finer control near the center
+    pub stick_look_exponent: f32,
+
+    // Orbit
+    pub focus: Vec3,
+    pub radius: f32,
+    pub min_radius: f32,
+    pub zoom_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    /// How fast focus and radius move to new targets, higher is faster
+    pub transition_speed: f32,
+    pub target_focus: Vec3,
+    pub target_radius: f32,
 }
 
 impl Default for CameraController {
     fn default() -> Self {
         Self {
             enabled: true,
-            sensitivity: 0.5,
-            key_forward: KeyCode::W,
-            key_back: KeyCode::S,
-            key_left: KeyCode::A,
-            key_right: KeyCode::D,
-            key_up: KeyCode::E,
-            key_down: KeyCode::Q,
-            key_run: KeyCode::LShift,
-            mouse_look: MouseButton::Right,
+            mode: CameraMode::Fly,
+            sensitivity: 0.008,
             walk_speed: 10.0,
             run_speed: 30.0,
-            friction: 0.3,
             pitch: None,
             yaw: None,
             velocity: Vec3::ZERO,
 
-            position_smoothness: 1.0,
-            rotation_smoothness: 100.0,
+            position_smoothness: 0.1,
+            damping: 0.1,
+            rotation_smoothness: 0.03,
+
+            stick_dead_zone: 0.15,
+            stick_look_speed: 2.5,
+            stick_look_exponent: 2.0,
+
+            focus: Vec3::ZERO,
+            radius: 5.0,
+            min_radius: 0.1,
+            zoom_sensitivity: 0.1,
+            pan_sensitivity: 1.0,
+            transition_speed: 8.0,
+            target_focus: Vec3::ZERO,
+            target_radius: 5.0,
+        }
+    }
+}
+
+impl CameraController {
+    /// Orbit camera around `focus`, radius is taken from the camera's starting position
+    pub fn orbit(focus: Vec3) -> Self {
+        Self {
+            mode: CameraMode::Orbit,
+            focus,
+            target_focus: focus,
+            ..Default::default()
+        }
+    }
+
+    /// Smoothly move the orbit to a new focus and radius
+    pub fn look_at(&mut self, focus: Vec3, radius: f32) {
+        self.target_focus = focus;
+        self.target_radius = radius.max(self.min_radius);
+    }
+
+    /// Match yaw, pitch and orbit focus to a transform set by something else, like a camera path
+    pub fn sync_to(&mut self, transform: &Transform) {
+        let (yaw, pitch, _roll) = yaw_pitch_roll(transform.rotation);
+        self.yaw = Some(yaw);
+        self.pitch = Some(pitch);
+        self.velocity = Vec3::ZERO;
+        if self.mode == CameraMode::Orbit {
+            let focus = transform.translation + transform.forward() * self.radius;
+            self.focus = focus;
+            self.target_focus = focus;
+            self.target_radius = self.radius;
         }
     }
 }
 
 #[allow(clippy::type_complexity)]
 fn init_camera_controller(
-    mut query: Query<(&Transform, &mut CameraController), (Added<CameraController>, With<Camera>)>,
+    mut query: Query<
+        (&mut Transform, &mut CameraController),
+        (Added<CameraController>, With<Camera>),
+    >,
 ) {
-    for (transform, mut options) in query.iter_mut() {
+    for (mut transform, mut options) in query.iter_mut() {
+        if options.mode == CameraMode::Orbit {
+            let radius = transform.translation.distance(options.focus);
+            options.radius = radius.max(options.min_radius);
+            options.target_radius = options.radius;
+            options.target_focus = options.focus;
+            *transform = transform.looking_at(options.focus, Vec3::Y);
+        }
+
         let (yaw, pitch, _roll) = yaw_pitch_roll(transform.rotation);
         options.pitch = Some(pitch);
         options.yaw = Some(yaw);
@@ -74,96 +225,235 @@ fn init_camera_controller(
 
 fn update_camera_controller(
     time: Res<Time>,
-    mut mouse_events: EventReader<MouseMotion>,
-    key_input: Res<Input<KeyCode>>,
-    mouse_input: Res<Input<MouseButton>>,
+    actions: Res<InputActions>,
     mut query: Query<(&mut Transform, &mut CameraController), With<Camera>>,
     mut windows: ResMut<Windows>,
 ) {
     let dt = time.delta_seconds();
     let window = windows.get_primary_mut().unwrap();
 
+    let look_motion = Vec2::new(actions.axis(CAMERA_LOOK_X), actions.axis(CAMERA_LOOK_Y));
+    let stick_look = Vec2::new(
+        actions.axis(CAMERA_STICK_LOOK_X),
+        actions.axis(CAMERA_STICK_LOOK_Y),
+    );
+
     for (mut transform, mut options) in query.iter_mut() {
         if !options.enabled {
             continue;
         }
 
-        // Handle key input
-        let mut axis_input = Vec3::ZERO;
-        if key_input.pressed(options.key_forward) {
-            axis_input.z += 1.0;
-        }
-        if key_input.pressed(options.key_back) {
-            axis_input.z -= 1.0;
-        }
-        if key_input.pressed(options.key_right) {
-            axis_input.x += 1.0;
-        }
-        if key_input.pressed(options.key_left) {
-            axis_input.x -= 1.0;
-        }
-        if key_input.pressed(options.key_up) {
-            axis_input.y += 1.0;
-        }
-        if key_input.pressed(options.key_down) {
-            axis_input.y -= 1.0;
-        }
-
-        // Apply movement update
-        if axis_input != Vec3::ZERO {
-            let max_speed = if key_input.pressed(options.key_run) {
-                options.run_speed
-            } else {
-                options.walk_speed
-            };
-            options.velocity = axis_input.normalize() * max_speed;
-        } else {
-            let friction = options.friction.clamp(0.0, 1.0);
-            options.velocity *= 1.0 - friction;
-            if options.velocity.length_squared() < 1e-6 {
-                options.velocity = Vec3::ZERO;
+        // Hide the cursor while dragging
+        for action in [CAMERA_LOOK, CAMERA_PAN] {
+            if actions.just_pressed(action) {
+                #[cfg(not(target="wasm32"))]
+                window.set_cursor_lock_mode(true);
+                window.set_cursor_visibility(false);
+            }
+            if actions.just_released(action) {
+                #[cfg(not(target="wasm32"))]
+                window.set_cursor_lock_mode(false);
+                window.set_cursor_visibility(true);
             }
         }
-        let forward = transform.forward();
-        let right = transform.right();
-        transform.translation += options.velocity.x * dt * right
-            + options.velocity.y * dt * Vec3::Y
-            + options.velocity.z * dt * forward;
 
-        // Handle mouse look on mouse button
+        // Handle mouse look while camera_look is held
         let mut mouse_delta = Vec2::ZERO;
-        if mouse_input.pressed(options.mouse_look) {
-            #[cfg(not(target="wasm32"))]
-            window.set_cursor_lock_mode(true);
-            window.set_cursor_visibility(false);
-        }
-        if mouse_input.just_released(options.mouse_look) {
-            #[cfg(not(target="wasm32"))]
-            window.set_cursor_lock_mode(false);
-            window.set_cursor_visibility(true);
-        }
-        if mouse_input.pressed(options.mouse_look) {
-            for mouse_event in mouse_events.iter() {
-                mouse_delta += mouse_event.delta;
-            }
+        if actions.pressed(CAMERA_LOOK) {
+            mouse_delta = look_motion;
         }
 
-        if mouse_delta != Vec2::ZERO {
+        // Look delta in radians, mouse y is down while stick y is up.
+        // Mouse motion is already per frame so only the stick is scaled by dt
+        let mut look_delta = Vec2::new(
+            -mouse_delta.x * options.sensitivity,
+            -mouse_delta.y * 0.5 * options.sensitivity,
+        );
+        let stick = stick_curve(
+            stick_look,
+            options.stick_dead_zone,
+            options.stick_look_exponent,
+        );
+        look_delta += Vec2::new(-stick.x, stick.y) * options.stick_look_speed * dt;
+
+        if look_delta != Vec2::ZERO {
             // Apply look update
             let (pitch, yaw) = (
-                (options.pitch.unwrap() - mouse_delta.y * 0.5 * options.sensitivity * dt).clamp(
+                (options.pitch.unwrap() + look_delta.y).clamp(
                     -0.99 * std::f32::consts::FRAC_PI_2,
                     0.99 * std::f32::consts::FRAC_PI_2,
                 ),
-                options.yaw.unwrap() - mouse_delta.x * options.sensitivity * dt,
+                options.yaw.unwrap() + look_delta.x,
             );
-
-            let target = Quat::from_euler(EulerRot::ZYX, 0.0, yaw, pitch);
-            transform.rotation = transform.rotation.lerp(target, 0.5);
-
             options.pitch = Some(pitch);
             options.yaw = Some(yaw);
         }
+
+        match options.mode {
+            CameraMode::Fly => {
+                // Keys give exactly 1.0 so the dead zone only affects analog input
+                let planar = stick_curve(
+                    Vec2::new(actions.axis(CAMERA_MOVE_X), actions.axis(CAMERA_MOVE_Z)),
+                    options.stick_dead_zone,
+                    1.0,
+                );
+                let axis_input = Vec3::new(
+                    planar.x,
+                    actions.axis(CAMERA_MOVE_Y).clamp(-1.0, 1.0),
+                    planar.y,
+                );
+                options.fly(&mut transform, axis_input, actions.pressed(CAMERA_RUN), dt);
+                options.rotate(&mut transform, dt);
+            }
+            CameraMode::Orbit => {
+                let pan = if actions.pressed(CAMERA_PAN) {
+                    look_motion
+                } else {
+                    Vec2::ZERO
+                };
+                let scroll = actions.axis(CAMERA_ZOOM);
+                orbit(&mut transform, &mut options, pan, scroll, dt);
+            }
+        }
+    }
+}
+
+impl CameraController {
+    /// Move for one frame, `axis_input` is camera space with x right, y up and z forward.
+    ///
+    /// Velocity eases toward the target exponentially and the displacement is integrated
+    /// exactly, so the distance covered doesn't depend on the frame rate.
+    pub fn fly(&mut self, transform: &mut Transform, axis_input: Vec3, run: bool, dt: f32) {
+        let (target, smoothness) = if axis_input != Vec3::ZERO {
+            let max_speed = if run { self.run_speed } else { self.walk_speed };
+            // Partial stick tilt moves slower
+            (
+                axis_input.clamp_length_max(1.0) * max_speed,
+                self.position_smoothness,
+            )
+        } else {
+            (Vec3::ZERO, self.damping)
+        };
+
+        // v(t) = target + (v0 - target) * e^(-t / smoothness), integrated over dt
+        let displacement = if smoothness > 0.0 {
+            let decay = (-dt / smoothness).exp();
+            let displacement = target * dt + (self.velocity - target) * smoothness * (1.0 - decay);
+            self.velocity = target + (self.velocity - target) * decay;
+            displacement
+        } else {
+            self.velocity = target;
+            target * dt
+        };
+        if self.velocity.length_squared() < 1e-6 && target == Vec3::ZERO {
+            self.velocity = Vec3::ZERO;
+        }
+
+        let forward = transform.forward();
+        let right = transform.right();
+        transform.translation +=
+            displacement.x * right + displacement.y * Vec3::Y + displacement.z * forward;
+    }
+
+    /// Slerp toward yaw and pitch for one frame
+    pub fn rotate(&self, transform: &mut Transform, dt: f32) {
+        let (yaw, pitch) = match (self.yaw, self.pitch) {
+            (Some(yaw), Some(pitch)) => (yaw, pitch),
+            _ => return,
+        };
+        let target = Quat::from_euler(EulerRot::ZYX, 0.0, yaw, pitch);
+        transform.rotation = transform
+            .rotation
+            .slerp(target, smoothing(self.rotation_smoothness, dt));
+    }
+}
+
+/// Fraction of the remaining distance to cover this frame, when easing over `seconds`
+fn smoothing(seconds: f32, dt: f32) -> f32 {
+    if seconds > 0.0 {
+        1.0 - (-dt / seconds).exp()
+    } else {
+        1.0
+    }
+}
+
+fn orbit(
+    transform: &mut Transform,
+    options: &mut CameraController,
+    pan: Vec2,
+    scroll: f32,
+    dt: f32,
+) {
+    // Zoom is relative to the current distance so it feels the same near and far
+    if scroll != 0.0 {
+        let radius = options.target_radius * (1.0 - scroll * options.zoom_sensitivity);
+        options.target_radius = radius.max(options.min_radius);
+    }
+
+    // Pan moves the focus in the camera plane, scaled so the focus sticks to the cursor
+    if pan != Vec2::ZERO {
+        let offset = (transform.right() * -pan.x + transform.up() * pan.y)
+            * options.pan_sensitivity
+            * options.radius
+            * 0.002;
+        options.target_focus += offset;
+        options.focus += offset;
+    }
+
+    // Ease toward targets, used when switching focus
+    let t = 1.0 - (-options.transition_speed * dt).exp();
+    options.focus = options.focus.lerp(options.target_focus, t);
+    options.radius += (options.target_radius - options.radius) * t;
+
+    options.rotate(transform, dt);
+    transform.translation = options.focus + transform.rotation * Vec3::Z * options.radius;
+}
+
+/// Radial dead zone, then rescale so the output still covers 0.0 - 1.0, then apply the curve
+fn stick_curve(value: Vec2, dead_zone: f32, exponent: f32) -> Vec2 {
+    let length = value.length().min(1.0);
+    if length <= dead_zone {
+        return Vec2::ZERO;
+    }
+    let scaled = (length - dead_zone) / (1.0 - dead_zone).max(f32::EPSILON);
+    value.normalize() * scaled.powf(exponent)
+}
+
+/// Fits the editor selection in view for orbit cameras
+fn frame_selected(
+    actions: Res<InputActions>,
+    inspector: Option<Res<Inspector>>,
+    bounds: Query<(&GlobalTransform, Option<&Aabb>)>,
+    mut cameras: Query<(&mut CameraController, Option<&PerspectiveProjection>), With<Camera>>,
+) {
+    if !actions.just_pressed(CAMERA_FRAME) {
+        return;
+    }
+    let selected = match inspector.and_then(|inspector| inspector.active) {
+        Some(selected) => selected,
+        None => return,
+    };
+    let (global, aabb) = match bounds.get(selected) {
+        Ok(bounds) => bounds,
+        Err(_) => return,
+    };
+
+    // Bounding sphere of the selection in world space
+    let (center, half_extents) = match aabb {
+        Some(aabb) => (Vec3::from(aabb.center), Vec3::from(aabb.half_extents)),
+        None => (Vec3::ZERO, Vec3::splat(0.5)),
+    };
+    let center = global.mul_vec3(center);
+    let radius = (half_extents * global.scale).length();
+
+    for (mut options, projection) in cameras.iter_mut() {
+        if !options.enabled || options.mode != CameraMode::Orbit {
+            continue;
+        }
+
+        let fov = projection.map_or(std::f32::consts::FRAC_PI_4, |p| p.fov);
+        let distance = radius / (fov * 0.5).sin();
+        options.look_at(center, distance);
     }
 }
 
@@ -185,3 +475,71 @@ fn yaw_pitch_roll(q: Quat) -> (f32, f32, f32) {
 
     (yaw, pitch, roll)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hold forward and turn for `hold` seconds, let go for `release` seconds.
+    /// Returns the distance travelled along the path and the final transform
+    fn simulate(fps: f32, hold: f32, release: f32) -> (f32, Transform) {
+        let dt = 1.0 / fps;
+        let mut transform = Transform::default();
+        let mut distance = 0.0;
+        let mut controller = CameraController {
+            yaw: Some(0.0),
+            pitch: Some(0.0),
+            ..Default::default()
+        };
+
+        let hold_frames = (hold * fps).round() as usize;
+        let release_frames = (release * fps).round() as usize;
+        for frame in 0..hold_frames + release_frames {
+            let holding = frame < hold_frames;
+            // Turn at a steady rate while holding, like a stick held to the side
+            if holding {
+                controller.yaw = Some(controller.yaw.unwrap() + 0.5 * dt);
+            }
+            let axis_input = if holding { Vec3::Z } else { Vec3::ZERO };
+            controller.rotate(&mut transform, dt);
+            let start = transform.translation;
+            controller.fly(&mut transform, axis_input, false, dt);
+            distance += start.distance(transform.translation);
+        }
+        (distance, transform)
+    }
+
+    #[test]
+    fn distance_does_not_depend_on_frame_rate() {
+        let (slow_distance, _) = simulate(30.0, 2.0, 1.0);
+        let (fast_distance, _) = simulate(144.0, 2.0, 1.0);
+
+        // Roughly 20 units of walking then a short coast
+        assert!(slow_distance > 15.0, "moved {}", slow_distance);
+        assert!(
+            (slow_distance - fast_distance).abs() < slow_distance * 1e-3,
+            "30 fps moved {}, 144 fps moved {}",
+            slow_distance,
+            fast_distance
+        );
+    }
+
+    #[test]
+    fn rotation_does_not_depend_on_frame_rate() {
+        let (_, slow) = simulate(30.0, 2.0, 1.0);
+        let (_, fast) = simulate(144.0, 2.0, 1.0);
+        let angle = slow.rotation.angle_between(fast.rotation);
+        assert!(angle < 1e-2, "rotations differ by {} rad", angle);
+    }
+
+    #[test]
+    fn damping_stops_the_camera() {
+        let mut transform = Transform::default();
+        let mut controller = CameraController::default();
+        controller.fly(&mut transform, Vec3::Z, false, 1.0);
+        for _ in 0..60 {
+            controller.fly(&mut transform, Vec3::ZERO, false, 1.0 / 60.0);
+        }
+        assert_eq!(controller.velocity, Vec3::ZERO);
+    }
+}
@@ -0,0 +1,506 @@
+use bevy::{
+    asset::AssetServerSettings, core::Time, prelude::*, reflect::TypeUuid,
+    transform::TransformSystem,
+};
+use bevy_egui::{
+    egui::{self, Grid, Window},
+    EguiContext,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{actions::*, camera_controller::*, editor::Editor, loaders::*};
+
+pub const CAMERA_PATH_PLAY: &str = "camera_path_play";
+
+pub struct CameraPathPlugin;
+
+impl Plugin for CameraPathPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonAssetPlugin::<CameraPath>::new(&["camera_path.ron"]))
+            .add_input_action(CAMERA_PATH_PLAY, &[InputBinding::Key(KeyCode::P)])
+            .add_system(toggle_playback)
+            .add_system(record_camera_path)
+            .add_system(camera_path_window)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                play_camera_path.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    /// Seconds from the start of the path
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+/// Keyframed camera flythrough, loaded from `*.camera_path.ron`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0f3c7a52-8e1d-4b6a-a3f0-5d2c9e7b1a48"]
+pub struct CameraPath {
+    /// Sorted by time
+    pub keyframes: Vec<CameraKeyframe>,
+    /// Start over after the last keyframe, add a final keyframe matching the first for a seamless
+    /// loop
+    #[serde(default)]
+    pub looping: bool,
+    /// Slow down at the start and end of the path
+    #[serde(default)]
+    pub ease: bool,
+}
+
+impl CameraPath {
+    /// Time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Add a keyframe keeping them sorted, replaces a keyframe at the same time
+    pub fn insert(&mut self, keyframe: CameraKeyframe) {
+        match self.keyframes.iter().position(|k| k.time >= keyframe.time) {
+            Some(i) if (self.keyframes[i].time - keyframe.time).abs() < 1e-4 => {
+                self.keyframes[i] = keyframe
+            }
+            Some(i) => self.keyframes.insert(i, keyframe),
+            None => self.keyframes.push(keyframe),
+        }
+    }
+
+    /// Remove the keyframe closest to `time`
+    pub fn remove_nearest(&mut self, time: f32) -> Option<CameraKeyframe> {
+        let (i, _) = self
+            .keyframes
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (a.time - time).abs().total_cmp(&(b.time - time).abs()))?;
+        Some(self.keyframes.remove(i))
+    }
+
+    /// Camera transform at `time`, Catmull-Rom through the translations and slerp between rotations
+    pub fn sample(&self, time: f32) -> Option<Transform> {
+        let keyframes = &self.keyframes;
+        let first = keyframes.first()?;
+        let duration = self.duration();
+        if keyframes.len() == 1 || duration <= first.time {
+            return Some(Transform {
+                translation: first.translation,
+                rotation: first.rotation,
+                ..Default::default()
+            });
+        }
+
+        // Normalize to the path, then ease over the whole path
+        let start = first.time;
+        let length = duration - start;
+        let mut t = if self.looping {
+            (time - start).rem_euclid(length)
+        } else {
+            (time - start).clamp(0.0, length)
+        } / length;
+        if self.ease {
+            t = t * t * (3.0 - 2.0 * t);
+        }
+        let time = start + t * length;
+
+        // Segment between keyframes i and i + 1
+        let i = keyframes
+            .iter()
+            .rposition(|k| k.time <= time)
+            .unwrap_or(0)
+            .min(keyframes.len() - 2);
+        let k1 = &keyframes[i];
+        let k2 = &keyframes[i + 1];
+        let p0 = keyframes[i.saturating_sub(1)].translation;
+        let p3 = keyframes[(i + 2).min(keyframes.len() - 1)].translation;
+
+        let span = k2.time - k1.time;
+        let s = if span > 0.0 {
+            ((time - k1.time) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        Some(Transform {
+            translation: catmull_rom(p0, k1.translation, k2.translation, p3, s),
+            rotation: k1.rotation.slerp(k2.rotation, s),
+            ..Default::default()
+        })
+    }
+}
+
+/// Uniform Catmull-Rom between `p1` and `p2`
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1)
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Plays or records a [`CameraPath`] on a camera, overrides any [`CameraController`] while playing
+#[derive(Component)]
+pub struct CameraPathPlayer {
+    pub path: Handle<CameraPath>,
+    /// Current time along the path
+    pub time: f32,
+    pub speed: f32,
+    pub playing: bool,
+    pub recording: bool,
+    /// Seconds between recorded keyframes
+    pub record_interval: f32,
+    /// Advance by this much every frame instead of real time, for repeatable screenshots
+    pub fixed_step: Option<f32>,
+    last_recorded: Option<f32>,
+}
+
+impl CameraPathPlayer {
+    pub fn new(path: Handle<CameraPath>) -> Self {
+        Self {
+            path,
+            time: 0.0,
+            speed: 1.0,
+            playing: false,
+            recording: false,
+            record_interval: 0.5,
+            fixed_step: None,
+            last_recorded: None,
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.recording = false;
+        self.playing = true;
+    }
+
+    /// Start recording from the end of the path
+    pub fn record(&mut self, path: &CameraPath) {
+        self.playing = false;
+        self.recording = true;
+        self.time = path.duration();
+        self.last_recorded = None;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.recording = false;
+    }
+}
+
+fn toggle_playback(actions: Res<InputActions>, mut query: Query<&mut CameraPathPlayer>) {
+    if !actions.just_pressed(CAMERA_PATH_PLAY) {
+        return;
+    }
+    for mut player in query.iter_mut() {
+        if player.playing {
+            player.stop();
+        } else {
+            player.play();
+        }
+    }
+}
+
+fn play_camera_path(
+    time: Res<Time>,
+    paths: Res<Assets<CameraPath>>,
+    mut query: Query<(
+        &mut Transform,
+        &mut CameraPathPlayer,
+        Option<&mut CameraController>,
+    )>,
+) {
+    for (mut transform, mut player, controller) in query.iter_mut() {
+        if !player.playing {
+            continue;
+        }
+        let path = match paths.get(&player.path) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let dt = player.fixed_step.unwrap_or_else(|| time.delta_seconds());
+        player.time += dt * player.speed;
+        if let Some(sample) = path.sample(player.time) {
+            transform.translation = sample.translation;
+            transform.rotation = sample.rotation;
+        }
+
+        // Rewind at the end so the next play starts over
+        if !path.looping && player.time >= path.duration() {
+            player.time = 0.0;
+            player.playing = false;
+        }
+
+        // Keep the controller in sync so it carries on from here when playback stops
+        if let Some(mut controller) = controller {
+            controller.sync_to(&transform);
+        }
+    }
+}
+
+fn record_camera_path(
+    time: Res<Time>,
+    mut paths: ResMut<Assets<CameraPath>>,
+    mut query: Query<(&Transform, &mut CameraPathPlayer)>,
+) {
+    for (transform, mut player) in query.iter_mut() {
+        if !player.recording {
+            continue;
+        }
+        if paths.get(&player.path).is_none() {
+            player.path = paths.add(CameraPath::default());
+        }
+
+        let due = player
+            .last_recorded
+            .map_or(true, |last| player.time - last >= player.record_interval);
+        if due {
+            let keyframe = CameraKeyframe {
+                time: player.time,
+                translation: transform.translation,
+                rotation: transform.rotation,
+            };
+            paths.get_mut(&player.path).unwrap().insert(keyframe);
+            player.last_recorded = Some(player.time);
+        }
+        player.time += time.delta_seconds();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn camera_path_window(
+    egui_ctx: Res<EguiContext>,
+    mut editor: ResMut<Editor>,
+    asset_server: Res<AssetServer>,
+    settings: Option<Res<AssetServerSettings>>,
+    mut paths: ResMut<Assets<CameraPath>>,
+    mut query: Query<(
+        &mut Transform,
+        &mut CameraPathPlayer,
+        Option<&mut CameraController>,
+        Option<&Name>,
+    )>,
+) {
+    if !editor.camera_path {
+        return;
+    }
+
+    let mut open = editor.camera_path;
+    Window::new("Camera Path")
+        .open(&mut open)
+        .vscroll(true)
+        .show(egui_ctx.ctx(), |ui| {
+            for (mut transform, mut player, controller, name) in query.iter_mut() {
+                if let Some(name) = name {
+                    ui.heading(name.as_str());
+                }
+                if paths.get(&player.path).is_none() {
+                    player.path = paths.add(CameraPath::default());
+                }
+                let path = paths.get_mut(&player.path).unwrap();
+
+                ui.horizontal(|ui| {
+                    if player.playing {
+                        if ui.button("Pause").clicked() {
+                            player.playing = false;
+                        }
+                    } else if ui.button("Play").clicked() {
+                        player.play();
+                    }
+                    if ui.button("Stop").clicked() {
+                        player.stop();
+                        player.time = 0.0;
+                    }
+                    let record = if player.recording {
+                        "Recording..."
+                    } else {
+                        "Record"
+                    };
+                    if ui.button(record).clicked() {
+                        if player.recording {
+                            player.stop();
+                        } else {
+                            player.record(path);
+                        }
+                    }
+                });
+
+                // Seeking moves the camera, the controller has to follow or it pulls it back
+                let mut seeked = false;
+                let duration = path.duration();
+                let mut t = player.time;
+                if ui
+                    .add(egui::Slider::new(&mut t, 0.0..=duration.max(0.0)).text("time"))
+                    .changed()
+                {
+                    player.time = t;
+                    if let Some(sample) = path.sample(t) {
+                        transform.translation = sample.translation;
+                        transform.rotation = sample.rotation;
+                        seeked = true;
+                    }
+                }
+                ui.add(egui::Slider::new(&mut player.speed, 0.1..=4.0).text("speed"));
+                ui.checkbox(&mut path.looping, "Loop");
+                ui.checkbox(&mut path.ease, "Ease in/out");
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add keyframe").clicked() {
+                        path.insert(CameraKeyframe {
+                            time: player.time,
+                            translation: transform.translation,
+                            rotation: transform.rotation,
+                        });
+                        // Next keyframe defaults to a second later
+                        player.time += 1.0;
+                    }
+                    if ui.button("Remove keyframe").clicked() {
+                        path.remove_nearest(player.time);
+                    }
+                });
+
+                let mut remove = None;
+                Grid::new("camera_keyframes").striped(true).show(ui, |ui| {
+                    for (i, keyframe) in path.keyframes.iter().enumerate() {
+                        ui.label(format!("{:.2}s", keyframe.time));
+                        if ui.small_button("Go to").clicked() {
+                            player.time = keyframe.time;
+                            transform.translation = keyframe.translation;
+                            transform.rotation = keyframe.rotation;
+                            seeked = true;
+                        }
+                        if ui.small_button("x").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = remove {
+                    path.keyframes.remove(i);
+                }
+                if let (true, Some(mut controller)) = (seeked, controller) {
+                    controller.sync_to(&transform);
+                }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Save").clicked() {
+                    let asset_path = asset_server
+                        .get_handle_path(&player.path)
+                        .map(|p| p.path().to_path_buf())
+                        .unwrap_or_else(|| "camera.camera_path.ron".into());
+                    let file = asset_file_path(settings.as_deref(), &asset_path);
+                    match save_ron_file(&file, &*path) {
+                        Ok(()) => info!("saved camera path to {}", file.display()),
+                        Err(err) => error!("failed to save {}: {}", file.display(), err),
+                    }
+                }
+                ui.separator();
+            }
+        });
+    editor.camera_path = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            translation: Vec3::new(x, 0.0, 0.0),
+            rotation: Quat::from_rotation_y(x),
+        }
+    }
+
+    /// Evenly spaced along x, so Catmull-Rom is a straight line through them
+    fn path(times: &[f32]) -> CameraPath {
+        CameraPath {
+            keyframes: times.iter().map(|&t| keyframe(t, t * 10.0)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn x_at(path: &CameraPath, time: f32) -> f32 {
+        path.sample(time).unwrap().translation.x
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn insert_keeps_keyframes_sorted() {
+        let mut path = CameraPath::default();
+        for time in [2.0, 0.0, 1.0, 3.0] {
+            path.insert(keyframe(time, time));
+        }
+        let times = path.keyframes.iter().map(|k| k.time).collect::<Vec<_>>();
+        assert_eq!(times, [0.0, 1.0, 2.0, 3.0]);
+
+        // Same time replaces
+        path.insert(keyframe(1.0, 5.0));
+        assert_eq!(path.keyframes.len(), 4);
+        assert_eq!(path.keyframes[1].translation.x, 5.0);
+    }
+
+    #[test]
+    fn remove_nearest() {
+        let mut path = path(&[0.0, 1.0, 2.0]);
+        assert_eq!(path.remove_nearest(1.2).map(|k| k.time), Some(1.0));
+        assert_eq!(path.remove_nearest(10.0).map(|k| k.time), Some(2.0));
+        assert_eq!(path.keyframes.len(), 1);
+
+        // NaN from a hand edited file doesn't panic
+        path.insert(keyframe(f32::NAN, 0.0));
+        assert!(path.remove_nearest(0.0).is_some());
+        assert!(CameraPath::default().remove_nearest(0.0).is_none());
+    }
+
+    #[test]
+    fn catmull_rom_goes_through_the_middle_points() {
+        let [p0, p1, p2, p3] = [0.0, 1.0, 3.0, 4.0].map(|x| Vec3::new(x, x * x, 0.0));
+        assert!(catmull_rom(p0, p1, p2, p3, 0.0).abs_diff_eq(p1, 1e-5));
+        assert!(catmull_rom(p0, p1, p2, p3, 1.0).abs_diff_eq(p2, 1e-5));
+        // Evenly spaced points on a line stay on it
+        let [p0, p1, p2, p3] = [0.0, 1.0, 2.0, 3.0].map(|x| Vec3::X * x);
+        assert!(catmull_rom(p0, p1, p2, p3, 0.25).abs_diff_eq(Vec3::X * 1.25, 1e-5));
+    }
+
+    #[test]
+    fn sample_hits_every_keyframe() {
+        let path = path(&[0.0, 1.0, 2.0, 3.0]);
+        for k in &path.keyframes {
+            let sample = path.sample(k.time).unwrap();
+            assert!(sample.translation.abs_diff_eq(k.translation, 1e-4));
+            assert!(sample.rotation.abs_diff_eq(k.rotation, 1e-4));
+        }
+        assert!(close(x_at(&path, 1.5), 15.0));
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn sample_clamps_or_loops_past_the_ends() {
+        let mut path = path(&[0.0, 1.0, 2.0]);
+        assert!(close(x_at(&path, -1.0), 0.0));
+        assert!(close(x_at(&path, 5.0), 20.0));
+
+        path.looping = true;
+        assert!(close(x_at(&path, 2.5), x_at(&path, 0.5)));
+        assert!(close(x_at(&path, -0.5), x_at(&path, 1.5)));
+    }
+
+    #[test]
+    fn ease_slows_down_the_ends() {
+        let linear = path(&[0.0, 1.0, 2.0, 3.0, 4.0]);
+        let eased = CameraPath {
+            ease: true,
+            ..linear.clone()
+        };
+        // Smoothstep of a quarter of the way
+        assert!(close(x_at(&eased, 1.0), x_at(&linear, 4.0 * 0.15625)));
+        assert!(close(x_at(&eased, 2.0), x_at(&linear, 2.0)));
+        assert!(close(x_at(&eased, 4.0), x_at(&linear, 4.0)));
+    }
+}
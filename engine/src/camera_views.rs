@@ -0,0 +1,273 @@
+use bevy::{
+    prelude::*,
+    render::camera::{ActiveCameras, Camera, CameraPlugin},
+};
+use bevy_egui::{
+    egui::{Grid, Window},
+    EguiContext,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{
+    actions::*, camera_controller::*, editor::Editor, follow_camera::*, loaders::*,
+    pan_zoom_camera::*,
+};
+
+/// Number keys recall bookmarks in order
+pub const CAMERA_BOOKMARKS: [&str; 9] = [
+    "camera_bookmark_1",
+    "camera_bookmark_2",
+    "camera_bookmark_3",
+    "camera_bookmark_4",
+    "camera_bookmark_5",
+    "camera_bookmark_6",
+    "camera_bookmark_7",
+    "camera_bookmark_8",
+    "camera_bookmark_9",
+];
+
+/// Saved viewpoints and switching between cameras
+pub struct CameraViewsPlugin {
+    /// RON file bookmarks are loaded from and saved to, ignored on wasm
+    pub path: PathBuf,
+}
+
+impl Plugin for CameraViewsPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let bookmarks = load_ron_file_or_default::<CameraBookmarks>(&self.path);
+        #[cfg(target_arch = "wasm32")]
+        let bookmarks = CameraBookmarks::default();
+
+        let keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        for (action, key) in CAMERA_BOOKMARKS.iter().zip(keys) {
+            app.add_input_action(action, &[InputBinding::Key(key)]);
+        }
+
+        app.insert_resource(bookmarks)
+            .insert_resource(CameraBookmarksPath(self.path.clone()))
+            .init_resource::<CurrentCamera>()
+            .add_system(recall_bookmark)
+            .add_system(switch_camera)
+            .add_system(camera_bookmarks_window);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+/// Named viewpoints, saved as RON
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmarks {
+    pub bookmarks: Vec<CameraBookmark>,
+}
+
+/// Where [`CameraBookmarks`] are saved
+pub struct CameraBookmarksPath(pub PathBuf);
+
+/// The camera that renders and takes input, `None` leaves every camera as spawned
+#[derive(Default)]
+pub struct CurrentCamera(pub Option<Entity>);
+
+/// Move a camera to a bookmark, keeping its controller in sync
+pub fn apply_bookmark(
+    bookmark: &CameraBookmark,
+    transform: &mut Transform,
+    controller: Option<&mut CameraController>,
+) {
+    transform.translation = bookmark.translation;
+    transform.rotation = bookmark.rotation;
+    if let Some(controller) = controller {
+        controller.sync_to(transform);
+    }
+}
+
+fn recall_bookmark(
+    egui_ctx: Res<EguiContext>,
+    actions: Res<InputActions>,
+    bookmarks: Res<CameraBookmarks>,
+    current: Res<CurrentCamera>,
+    mut cameras: Query<(Entity, &mut Transform, Option<&mut CameraController>), With<Camera>>,
+) {
+    // Number keys typed into a text field, like a bookmark name, aren't shortcuts
+    if egui_ctx.ctx().wants_keyboard_input() {
+        return;
+    }
+    let bookmark = match CAMERA_BOOKMARKS
+        .iter()
+        .position(|action| actions.just_pressed(action))
+        .and_then(|i| bookmarks.bookmarks.get(i))
+    {
+        Some(bookmark) => bookmark,
+        None => return,
+    };
+
+    for (entity, mut transform, mut controller) in cameras.iter_mut() {
+        let is_current = match current.0 {
+            Some(current) => current == entity,
+            None => controller.is_some(),
+        };
+        if is_current {
+            apply_bookmark(bookmark, &mut transform, controller.as_deref_mut());
+            break;
+        }
+    }
+}
+
+/// Renders from the current camera and enables input only on it
+fn switch_camera(
+    current: Res<CurrentCamera>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut cameras: Query<(
+        Entity,
+        &Camera,
+        Option<&mut CameraController>,
+        Option<&mut PanZoomCamera>,
+        Option<&mut FollowCamera>,
+    )>,
+) {
+    if !current.is_changed() {
+        return;
+    }
+    let current = match current.0 {
+        Some(current) => current,
+        None => return,
+    };
+
+    for (entity, camera, controller, pan_zoom, follow) in cameras.iter_mut() {
+        let enabled = entity == current;
+        if let Some(mut controller) = controller {
+            controller.enabled = enabled;
+        }
+        if let Some(mut pan_zoom) = pan_zoom {
+            pan_zoom.enabled = enabled;
+        }
+        if let Some(mut follow) = follow {
+            follow.enabled = enabled;
+        }
+        if enabled {
+            if let Some(active) = camera
+                .name
+                .as_ref()
+                .and_then(|name| active_cameras.get_mut(name))
+            {
+                active.entity = Some(entity);
+            }
+        }
+    }
+}
+
+/// Pick the current camera out of the 2D and 3D cameras, used by the editor toolbar
+pub fn camera_selector_ui<'a>(
+    ui: &mut bevy_egui::egui::Ui,
+    current: &mut CurrentCamera,
+    cameras: impl Iterator<Item = (Entity, &'a Camera, Option<&'a Name>)>,
+) {
+    for (entity, camera, name) in cameras {
+        let name = match camera.name.as_deref() {
+            Some(CameraPlugin::CAMERA_3D) | Some(CameraPlugin::CAMERA_2D) => name
+                .map(|name| name.as_str().to_string())
+                .unwrap_or_else(|| format!("Camera {}", entity.id())),
+            _ => continue,
+        };
+        if ui.radio(current.0 == Some(entity), name).clicked() {
+            current.0 = Some(entity);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn camera_bookmarks_window(
+    egui_ctx: Res<EguiContext>,
+    mut editor: ResMut<Editor>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    path: Res<CameraBookmarksPath>,
+    current: Res<CurrentCamera>,
+    mut cameras: Query<(Entity, &mut Transform, Option<&mut CameraController>), With<Camera>>,
+    mut new_name: Local<String>,
+) {
+    if !editor.camera_bookmarks {
+        return;
+    }
+
+    // Same camera the number keys move
+    let camera = cameras
+        .iter_mut()
+        .find(|(entity, _, controller)| match current.0 {
+            Some(current) => current == *entity,
+            None => controller.is_some(),
+        });
+    let (mut transform, mut controller) = match camera {
+        Some((_, transform, controller)) => (transform, controller),
+        None => return,
+    };
+
+    let mut open = editor.camera_bookmarks;
+    Window::new("Camera Bookmarks")
+        .open(&mut open)
+        .vscroll(true)
+        .show(egui_ctx.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut *new_name);
+                if ui.button("Save current view").clicked() {
+                    let name = if new_name.is_empty() {
+                        format!("View {}", bookmarks.bookmarks.len() + 1)
+                    } else {
+                        std::mem::take(&mut *new_name)
+                    };
+                    bookmarks.bookmarks.push(CameraBookmark {
+                        name,
+                        translation: transform.translation,
+                        rotation: transform.rotation,
+                    });
+                }
+            });
+
+            let mut remove = None;
+            Grid::new("camera_bookmarks").striped(true).show(ui, |ui| {
+                for (i, bookmark) in bookmarks.bookmarks.iter().enumerate() {
+                    // Bookmarks past 9 have no key
+                    if i < CAMERA_BOOKMARKS.len() {
+                        ui.label(format!("{}", i + 1));
+                    } else {
+                        ui.label("");
+                    }
+                    ui.label(&bookmark.name);
+                    if ui.small_button("Go to").clicked() {
+                        apply_bookmark(bookmark, &mut transform, controller.as_deref_mut());
+                    }
+                    if ui.small_button("x").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                bookmarks.bookmarks.remove(i);
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Save").clicked() {
+                match save_ron_file(&path.0, &*bookmarks) {
+                    Ok(()) => info!("saved camera bookmarks to {}", path.0.display()),
+                    Err(err) => error!("failed to save {}: {}", path.0.display(), err),
+                }
+            }
+        });
+    editor.camera_bookmarks = open;
+}
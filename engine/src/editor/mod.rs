@@ -11,10 +11,11 @@ pub enum EditorState {
     Disabled,
 }
 
+/// The entity currently selected in the editor
 #[derive(Inspectable, Default)]
-struct Inspector {
+pub struct Inspector {
     #[inspectable(deletable = false)]
-    active: Option<Entity>,
+    pub active: Option<Entity>,
 }
 
 #[derive(Inspectable, Default)]
@@ -24,6 +25,50 @@ pub struct Editor {
     // Windows
     pub egui_settings: bool,
     pub egui_inspection: bool,
+    pub input_bindings: bool,
+    pub camera_path: bool,
+    pub camera_bookmarks: bool,
+}
+
+/// Windows added by games, listed in the toolbar's Windows menu after the built in ones
+#[derive(Default)]
+pub struct EditorWindows {
+    windows: Vec<(String, bool)>,
+}
+
+impl EditorWindows {
+    pub fn is_open(&self, name: &str) -> bool {
+        self.windows
+            .iter()
+            .any(|(window, open)| window == name && *open)
+    }
+
+    pub fn set_open(&mut self, name: &str, open: bool) {
+        if let Some((_, o)) = self.windows.iter_mut().find(|(window, _)| window == name) {
+            *o = open;
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut bool)> {
+        self.windows
+            .iter_mut()
+            .map(|(name, open)| (name.as_str(), open))
+    }
+}
+
+pub trait AppEditorWindows {
+    /// Declare a window the editor can toggle, check it with [`EditorWindows::is_open`]
+    fn add_editor_window(&mut self, name: &str) -> &mut App;
+}
+
+impl AppEditorWindows for App {
+    fn add_editor_window(&mut self, name: &str) -> &mut App {
+        let mut windows = self.world.get_resource_mut::<EditorWindows>().unwrap();
+        if !windows.windows.iter().any(|(window, _)| window == name) {
+            windows.windows.push((name.to_string(), false));
+        }
+        self
+    }
 }
 
 /// Provides Bevy Editor for Debugging
@@ -31,7 +76,8 @@ pub struct EditorPlugin;
 impl Plugin for EditorPlugin {
     fn build(&self, app: &mut App) {
 
-        app.add_plugin(InspectorPlugin::<Editor>::new().open(false))
+        app.init_resource::<EditorWindows>()
+            .add_plugin(InspectorPlugin::<Editor>::new().open(false))
             .add_plugin(InspectorPlugin::<Inspector>::new().open(false))
             .add_state(EditorState::Disabled)
             .add_system_set(SystemSet::on_enter(EditorState::Playing).with_system(setup))
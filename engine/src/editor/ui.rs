@@ -9,7 +9,8 @@ use bevy_egui::{
 };
 use bevy_inspector_egui::{WorldInspectorParams, plugin::InspectorWindows};
 
-use super::Editor;
+use super::{Editor, EditorWindows};
+use crate::camera_views::{camera_selector_ui, CurrentCamera};
 
 #[allow(clippy::too_many_arguments)]
 pub fn toolbar_system(
@@ -20,6 +21,9 @@ pub fn toolbar_system(
     mut inspector_windows: ResMut<InspectorWindows>,
     diagnostics: Res<Diagnostics>,
     mut windows: ResMut<Editor>,
+    mut editor_windows: ResMut<EditorWindows>,
+    mut current_camera: ResMut<CurrentCamera>,
+    cameras: Query<(Entity, &Camera, Option<&Name>)>,
 ) {
     use bevy_inspector_egui::egui::Window;
 
@@ -38,6 +42,19 @@ pub fn toolbar_system(
                     &mut windows.egui_inspection,
                     "Egui Inspection",
                 ));
+                ui.add(Checkbox::new(&mut windows.input_bindings, "Input Bindings"));
+                ui.add(Checkbox::new(&mut windows.camera_path, "Camera Path"));
+                ui.add(Checkbox::new(
+                    &mut windows.camera_bookmarks,
+                    "Camera Bookmarks",
+                ));
+                for (name, open) in editor_windows.iter_mut() {
+                    ui.add(Checkbox::new(open, name));
+                }
+            });
+
+            menu::menu(ui, "Camera", |ui| {
+                camera_selector_ui(ui, &mut current_camera, cameras.iter());
             });
 
             menu::menu(ui, "Resources", |ui| {
@@ -0,0 +1,263 @@
+use bevy::{core::Time, prelude::*, render::primitives::Aabb, transform::TransformSystem};
+
+use crate::{actions::*, camera_controller::*};
+
+pub const CAMERA_NEXT_TARGET: &str = "camera_next_target";
+pub const CAMERA_CLEAR_TARGET: &str = "camera_clear_target";
+
+pub struct FollowCameraPlugin;
+
+impl Plugin for FollowCameraPlugin {
+    fn build(&self, app: &mut App) {
+        use InputBinding::*;
+
+        app.add_input_action(CAMERA_NEXT_TARGET, &[Key(KeyCode::Tab)])
+            .add_input_action(CAMERA_CLEAR_TARGET, &[Key(KeyCode::Back)])
+            .add_system(cycle_follow_target)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                follow_target.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Entities a [`FollowCamera`] can switch to with camera_next_target
+#[derive(Component, Default)]
+pub struct FollowTarget;
+
+/// Tracks `target`, does nothing while there is no target so other controllers keep working.
+/// The target is cleared when it despawns
+#[derive(Component)]
+pub struct FollowCamera {
+    pub enabled: bool,
+    pub target: Option<Entity>,
+    /// Camera position relative to the target
+    pub offset: Vec3,
+    /// Rotate `offset` with the target for a third-person view
+    pub local_offset: bool,
+    /// Turn to face the target, leave off for top down orthographic cameras
+    pub look_at_target: bool,
+    /// Only follow in x and y, keeping the camera's z, for 2D games
+    pub planar: bool,
+    /// Seconds to close most of the distance to the target, 0.0 snaps
+    pub damping: f32,
+    /// Lead the target by this many seconds of its velocity
+    pub look_ahead: f32,
+    /// Snap instead of damping when the target moves further than this in one frame, like
+    /// wrapping to the other side of the map
+    pub snap_distance: f32,
+    /// Pull the camera in front of anything with an `Aabb` between it and the target, the
+    /// target and its children are skipped
+    pub collision: bool,
+    /// Distance kept from geometry when pulled in
+    pub collision_margin: f32,
+    last_target_position: Option<Vec3>,
+    target_velocity: Vec3,
+}
+
+impl Default for FollowCamera {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target: None,
+            offset: Vec3::new(0.0, 2.0, 6.0),
+            local_offset: false,
+            look_at_target: true,
+            planar: false,
+            damping: 0.2,
+            look_ahead: 0.0,
+            snap_distance: f32::INFINITY,
+            collision: false,
+            collision_margin: 0.2,
+            last_target_position: None,
+            target_velocity: Vec3::ZERO,
+        }
+    }
+}
+
+impl FollowCamera {
+    /// Third-person rig behind and above the target
+    pub fn third_person(target: Entity) -> Self {
+        Self {
+            target: Some(target),
+            local_offset: true,
+            look_ahead: 0.3,
+            collision: true,
+            ..Default::default()
+        }
+    }
+
+    /// Top down rig keeping the camera's height, for orthographic cameras
+    pub fn top_down() -> Self {
+        Self {
+            offset: Vec3::ZERO,
+            look_at_target: false,
+            planar: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_target(&mut self, target: Option<Entity>) {
+        self.target = target;
+        self.last_target_position = None;
+        self.target_velocity = Vec3::ZERO;
+    }
+}
+
+/// camera_next_target moves to the next [`FollowTarget`] then back to no target,
+/// camera_clear_target stops following
+fn cycle_follow_target(
+    actions: Res<InputActions>,
+    targets: Query<Entity, With<FollowTarget>>,
+    mut cameras: Query<&mut FollowCamera>,
+) {
+    if actions.just_pressed(CAMERA_CLEAR_TARGET) {
+        for mut camera in cameras.iter_mut() {
+            camera.set_target(None);
+        }
+    }
+    if !actions.just_pressed(CAMERA_NEXT_TARGET) {
+        return;
+    }
+
+    let mut targets = targets.iter().collect::<Vec<_>>();
+    targets.sort();
+    for mut camera in cameras.iter_mut() {
+        let next = match camera.target {
+            None => targets.first().copied(),
+            Some(current) => targets
+                .iter()
+                .position(|e| *e == current)
+                .and_then(|i| targets.get(i + 1).copied()),
+        };
+        camera.set_target(next);
+    }
+}
+
+fn follow_target(
+    time: Res<Time>,
+    targets: Query<&GlobalTransform>,
+    parents: Query<&Parent>,
+    colliders: Query<(Entity, &GlobalTransform, &Aabb), With<Handle<Mesh>>>,
+    mut cameras: Query<(
+        &mut Transform,
+        &mut FollowCamera,
+        Option<&mut CameraController>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (mut transform, mut follow, controller) in cameras.iter_mut() {
+        if !follow.enabled {
+            continue;
+        }
+        let target = match follow.target {
+            Some(target) => target,
+            None => continue,
+        };
+
+        // Target despawned, like the snake on game over
+        let target_transform = match targets.get(target) {
+            Ok(target_transform) => target_transform,
+            Err(_) => {
+                follow.set_target(None);
+                continue;
+            }
+        };
+        let target_position = target_transform.translation;
+
+        // Velocity from movement since last frame, smoothed since targets often move in steps.
+        // Jumps past `snap_distance` aren't movement, the camera snaps to them instead
+        let mut snap = false;
+        if let Some(last) = follow.last_target_position {
+            if last.distance(target_position) > follow.snap_distance {
+                snap = true;
+                follow.target_velocity = Vec3::ZERO;
+            } else if dt > 0.0 {
+                let velocity = (target_position - last) / dt;
+                follow.target_velocity =
+                    follow.target_velocity.lerp(velocity, (dt * 10.0).min(1.0));
+            }
+        }
+        follow.last_target_position = Some(target_position);
+
+        let focus = target_position + follow.target_velocity * follow.look_ahead;
+        let offset = if follow.local_offset {
+            target_transform.rotation * follow.offset
+        } else {
+            follow.offset
+        };
+        let mut desired = focus + offset;
+        if follow.planar {
+            desired.z = transform.translation.z;
+        }
+
+        if follow.collision {
+            let distance = offset.length();
+            if distance > f32::EPSILON {
+                let dir = offset / distance;
+                let hit = colliders
+                    .iter()
+                    .filter(|(entity, ..)| !is_descendant(*entity, target, &parents))
+                    .filter_map(|(_, global, aabb)| ray_aabb(focus, dir, global, aabb))
+                    .filter(|t| *t < distance)
+                    .fold(distance, f32::min);
+                if hit < distance {
+                    desired = focus + dir * (hit - follow.collision_margin).max(0.0);
+                }
+            }
+        }
+
+        // Exponential damping, the same at any frame rate
+        let t = if follow.damping > 0.0 && !snap {
+            1.0 - (-dt / follow.damping).exp()
+        } else {
+            1.0
+        };
+        transform.translation = transform.translation.lerp(desired, t);
+        if follow.look_at_target && transform.translation.distance_squared(focus) > 1e-6 {
+            let look = transform.looking_at(focus, Vec3::Y).rotation;
+            transform.rotation = transform.rotation.slerp(look, t);
+        }
+
+        if let Some(mut controller) = controller {
+            controller.sync_to(&transform);
+        }
+    }
+}
+
+/// Is `entity` `ancestor` or somewhere below it in the hierarchy
+fn is_descendant(mut entity: Entity, ancestor: Entity, parents: &Query<&Parent>) -> bool {
+    loop {
+        if entity == ancestor {
+            return true;
+        }
+        match parents.get(entity) {
+            Ok(parent) => entity = parent.0,
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Distance along the ray to an oriented bounding box, ignores boxes the ray starts in
+fn ray_aabb(origin: Vec3, dir: Vec3, global: &GlobalTransform, aabb: &Aabb) -> Option<f32> {
+    let to_local = global.compute_matrix().inverse();
+    let local_origin = to_local.transform_point3(origin);
+    let local_dir = to_local.transform_vector3(dir);
+    let min = Vec3::from(aabb.center) - Vec3::from(aabb.half_extents);
+    let max = Vec3::from(aabb.center) + Vec3::from(aabb.half_extents);
+
+    if local_origin.cmpge(min).all() && local_origin.cmple(max).all() {
+        return None;
+    }
+
+    // Slab test, t is the same along the local and world ray since the transform is affine
+    let inv = local_dir.recip();
+    let t1 = (min - local_origin) * inv;
+    let t2 = (max - local_origin) * inv;
+    let near = t1.min(t2).max_element();
+    let far = t1.max(t2).min_element();
+    if near > far || far < 0.0 {
+        return None;
+    }
+    Some(near)
+}
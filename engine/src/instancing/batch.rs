@@ -0,0 +1,192 @@
+// CPU side of instancing, no render types so it can be checked without a GPU
+use bevy::{prelude::*, utils::HashMap};
+use bytemuck::{Pod, Zeroable};
+use std::hash::Hash;
+
+/// Per instance vertex data, the model matrix by column then a linear color
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct InstanceData {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+impl InstanceData {
+    pub fn new(transform: Mat4, color: Color) -> Self {
+        Self {
+            model: transform.to_cols_array_2d(),
+            color: color.as_linear_rgba_f32(),
+        }
+    }
+}
+
+/// Everything drawn with one mesh and material
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BatchKey {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstanceBatch<K = BatchKey> {
+    pub key: K,
+    pub instances: Vec<InstanceData>,
+}
+
+/// Instances grouped by key, one instanced draw per batch. Batches keep the order their key was
+/// first pushed and instances keep the order they were pushed, so output is deterministic
+pub struct InstanceBatches<K = BatchKey> {
+    batches: Vec<InstanceBatch<K>>,
+    index: HashMap<K, usize>,
+}
+
+impl<K> Default for InstanceBatches<K> {
+    fn default() -> Self {
+        Self {
+            batches: Vec::new(),
+            index: HashMap::default(),
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash> InstanceBatches<K> {
+    /// Start a new frame, batches left empty last frame are dropped and the rest keep their
+    /// allocations
+    pub fn clear(&mut self) {
+        self.batches.retain(|batch| !batch.instances.is_empty());
+        self.index.clear();
+        for (i, batch) in self.batches.iter_mut().enumerate() {
+            batch.instances.clear();
+            self.index.insert(batch.key.clone(), i);
+        }
+    }
+
+    pub fn push(&mut self, key: K, instance: InstanceData) {
+        let i = match self.index.get(&key) {
+            Some(&i) => i,
+            None => {
+                self.batches.push(InstanceBatch {
+                    key: key.clone(),
+                    instances: Vec::new(),
+                });
+                self.index.insert(key, self.batches.len() - 1);
+                self.batches.len() - 1
+            }
+        };
+        self.batches[i].instances.push(instance);
+    }
+
+    /// Batches with at least one instance
+    pub fn iter(&self) -> impl Iterator<Item = &InstanceBatch<K>> {
+        self.batches
+            .iter()
+            .filter(|batch| !batch.instances.is_empty())
+    }
+
+    pub fn get(&self, key: &K) -> Option<&InstanceBatch<K>> {
+        self.index
+            .get(key)
+            .map(|&i| &self.batches[i])
+            .filter(|batch| !batch.instances.is_empty())
+    }
+
+    /// Number of draws this frame
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.instances.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+
+    fn instance(x: f32, color: Color) -> InstanceData {
+        InstanceData::new(Mat4::from_translation(Vec3::new(x, 0.0, 0.0)), color)
+    }
+
+    fn key(material: &Handle<StandardMaterial>) -> BatchKey {
+        BatchKey {
+            mesh: Handle::weak(HandleId::random::<Mesh>()),
+            material: material.clone_weak(),
+        }
+    }
+
+    fn new_material() -> Handle<StandardMaterial> {
+        Handle::weak(HandleId::random::<StandardMaterial>())
+    }
+
+    #[test]
+    fn one_batch_per_mesh_and_material() {
+        let material = new_material();
+        let (cube, sphere) = (key(&material), key(&material));
+        // 10k cubes and a few spheres, interleaved
+        let mut batches = InstanceBatches::default();
+        for i in 0..10_000 {
+            batches.push(cube.clone(), instance(i as f32, Color::PINK));
+            if i % 1000 == 0 {
+                batches.push(sphere.clone(), instance(i as f32, Color::WHITE));
+            }
+        }
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches.instance_count(), 10_010);
+        // First pushed order
+        assert!(batches.iter().map(|batch| &batch.key).eq([&cube, &sphere]));
+
+        let cubes = batches.get(&cube).unwrap();
+        assert!(cubes
+            .instances
+            .iter()
+            .enumerate()
+            .all(|(i, data)| data.model[3][0] == i as f32));
+        assert_eq!(cubes.instances[0].color, Color::PINK.as_linear_rgba_f32());
+    }
+
+    #[test]
+    fn material_splits_batches() {
+        let cube = key(&new_material());
+        let red = BatchKey {
+            mesh: cube.mesh.clone_weak(),
+            material: new_material(),
+        };
+        let mut batches = InstanceBatches::default();
+        batches.push(cube, instance(0.0, Color::WHITE));
+        batches.push(red, instance(0.0, Color::RED));
+        assert_eq!(batches.len(), 2);
+    }
+
+    #[test]
+    fn empty_batches_are_not_drawn() {
+        let material = new_material();
+        let (cube, sphere) = (key(&material), key(&material));
+        let mut batches = InstanceBatches::default();
+        batches.push(cube.clone(), instance(0.0, Color::WHITE));
+        batches.push(sphere.clone(), instance(0.0, Color::WHITE));
+
+        // Next frame only has spheres
+        batches.clear();
+        batches.push(sphere.clone(), instance(0.0, Color::WHITE));
+        assert_eq!(batches.len(), 1);
+        assert!(batches.get(&cube).is_none());
+        assert_eq!(batches.instance_count(), 1);
+
+        // Empty for a whole frame so it's dropped, coming back appends it after the spheres
+        batches.clear();
+        batches.push(sphere.clone(), instance(0.0, Color::WHITE));
+        batches.push(cube.clone(), instance(0.0, Color::WHITE));
+        assert!(batches.iter().map(|batch| &batch.key).eq([&sphere, &cube]));
+    }
+
+    #[test]
+    fn instance_data_is_five_vec4s() {
+        assert_eq!(std::mem::size_of::<InstanceData>(), 80);
+    }
+}
@@ -0,0 +1,44 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+
+    [[location(3)]] i_model_0: vec4<f32>;
+    [[location(4)]] i_model_1: vec4<f32>;
+    [[location(5)]] i_model_2: vec4<f32>;
+    [[location(6)]] i_model_3: vec4<f32>;
+    [[location(7)]] i_color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_normal: vec3<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let model = mat4x4<f32>(vertex.i_model_0, vertex.i_model_1, vertex.i_model_2, vertex.i_model_3);
+    let world_position = mesh.model * model * vec4<f32>(vertex.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_position;
+    // Fine for uniform scale, which is all the instanced users need
+    out.world_normal = (model * vec4<f32>(vertex.normal, 0.0)).xyz;
+    out.color = vertex.i_color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Fixed light from above so shapes read without the full PBR lights
+    let light = normalize(vec3<f32>(0.4, 1.0, 0.6));
+    let diffuse = max(dot(normalize(in.world_normal), light), 0.0);
+    return vec4<f32>(in.color.rgb * (0.6 + 0.4 * diffuse), in.color.a);
+}
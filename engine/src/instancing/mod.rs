@@ -0,0 +1,84 @@
+// Draw many entities sharing a mesh and material in one instanced draw call
+mod batch;
+mod render;
+
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_inspector_egui::{Inspectable, InspectableRegistry};
+
+pub use batch::*;
+
+pub struct InstancingPlugin;
+
+impl Plugin for InstancingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InstanceBatches>().add_system_to_stage(
+            CoreStage::PostUpdate,
+            batch_instances.after(TransformSystem::TransformPropagate),
+        );
+
+        let mut registry = app.world.get_resource_mut::<InspectableRegistry>().unwrap();
+        registry.register::<Instanced>();
+
+        render::build(app);
+    }
+}
+
+/// Batched with every other entity using the same mesh and material instead of getting its own
+/// draw. Only the material's base color is used, multiplied with `color`, lighting is a simple
+/// fixed direction and textures are ignored
+#[derive(Component, Inspectable, Clone, Debug)]
+pub struct Instanced {
+    pub material: Handle<StandardMaterial>,
+    pub color: Color,
+}
+
+impl Default for Instanced {
+    fn default() -> Self {
+        Self {
+            material: Default::default(),
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Use in place of a `PbrBundle`, there is no material component so the PBR renderer skips it
+#[derive(Bundle, Clone, Default)]
+pub struct InstancedBundle {
+    pub mesh: Handle<Mesh>,
+    pub instanced: Instanced,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+}
+
+fn batch_instances(
+    mut batches: ResMut<InstanceBatches>,
+    materials: Res<Assets<StandardMaterial>>,
+    query: Query<(&Handle<Mesh>, &Instanced, &GlobalTransform, &Visibility)>,
+) {
+    batches.clear();
+    for (mesh, instanced, transform, visibility) in query.iter() {
+        if !visibility.is_visible {
+            continue;
+        }
+        // Tint in linear space
+        let base = materials
+            .get(&instanced.material)
+            .map_or(Color::WHITE, |material| material.base_color)
+            .as_linear_rgba_f32();
+        let tint = instanced.color.as_linear_rgba_f32();
+        let color = Color::rgba_linear(
+            base[0] * tint[0],
+            base[1] * tint[1],
+            base[2] * tint[2],
+            base[3] * tint[3],
+        );
+        batches.push(
+            BatchKey {
+                mesh: mesh.clone_weak(),
+                material: instanced.material.clone_weak(),
+            },
+            InstanceData::new(transform.compute_matrix(), color),
+        );
+    }
+}
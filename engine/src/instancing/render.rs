@@ -0,0 +1,207 @@
+// GPU side of instancing, each batch becomes a render world entity with its own instance buffer
+use bevy::{
+    core_pipeline::Opaque3d,
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::GpuBufferInfo,
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::{ExtractedView, Msaa},
+        RenderApp, RenderStage,
+    },
+};
+
+use super::{InstanceBatches, InstanceData};
+
+pub const INSTANCING_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5c1e_d0c5_1a57_a4c3);
+
+pub(super) fn build(app: &mut App) {
+    let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+    shaders.set_untracked(
+        INSTANCING_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("instancing.wgsl")),
+    );
+
+    app.sub_app_mut(RenderApp)
+        .add_render_command::<Opaque3d, DrawInstanced>()
+        .init_resource::<InstancingPipeline>()
+        .init_resource::<SpecializedPipelines<InstancingPipeline>>()
+        .add_system_to_stage(RenderStage::Extract, extract_batches)
+        .add_system_to_stage(RenderStage::Prepare, prepare_instance_buffers)
+        .add_system_to_stage(RenderStage::Queue, queue_batches);
+}
+
+#[derive(Component)]
+struct ExtractedBatch(Vec<InstanceData>);
+
+#[derive(Component)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+// Render world entities are cleared every frame, so each batch is spawned fresh
+fn extract_batches(mut commands: Commands, batches: Res<InstanceBatches>) {
+    for batch in batches.iter() {
+        commands.spawn_bundle((
+            batch.key.mesh.clone_weak(),
+            // Instances carry their own transform, the mesh uniform is only there for the bind
+            // group layout
+            MeshUniform {
+                transform: Mat4::IDENTITY,
+                inverse_transpose_model: Mat4::IDENTITY,
+                flags: 0,
+            },
+            ExtractedBatch(batch.instances.clone()),
+        ));
+    }
+}
+
+fn prepare_instance_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    batches: Query<(Entity, &ExtractedBatch)>,
+) {
+    for (entity, batch) in batches.iter() {
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("instance buffer"),
+            contents: bytemuck::cast_slice(&batch.0),
+            usage: BufferUsages::VERTEX,
+        });
+        commands.entity(entity).insert(InstanceBuffer {
+            buffer,
+            length: batch.0.len(),
+        });
+    }
+}
+
+fn queue_batches(
+    draw_functions: Res<DrawFunctions<Opaque3d>>,
+    instancing_pipeline: Res<InstancingPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedPipelines<InstancingPipeline>>,
+    mut pipeline_cache: ResMut<RenderPipelineCache>,
+    batches: Query<Entity, With<ExtractedBatch>>,
+    mut views: Query<&mut RenderPhase<Opaque3d>, With<ExtractedView>>,
+) {
+    let draw_instanced = draw_functions.read().get_id::<DrawInstanced>().unwrap();
+    let key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+    let pipeline = pipelines.specialize(&mut pipeline_cache, &instancing_pipeline, key);
+
+    for mut opaque_phase in views.iter_mut() {
+        for entity in batches.iter() {
+            opaque_phase.add(Opaque3d {
+                entity,
+                pipeline,
+                draw_function: draw_instanced,
+                // Batches span the scene, there's no single depth to sort them by
+                distance: 0.0,
+            });
+        }
+    }
+}
+
+pub struct InstancingPipeline {
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for InstancingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh_pipeline: world.get_resource::<MeshPipeline>().unwrap().clone(),
+        }
+    }
+}
+
+impl SpecializedPipeline for InstancingPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut descriptor = self.mesh_pipeline.specialize(key);
+        let shader = INSTANCING_SHADER_HANDLE.typed::<Shader>();
+        descriptor.vertex.shader = shader.clone();
+
+        // Model matrix columns at 3..=6, color at 7
+        let vec4 = VertexFormat::Float32x4.size();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceData>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: (0..5)
+                .map(|i| VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: vec4 * i,
+                    shader_location: 3 + i as u32,
+                })
+                .collect(),
+        });
+        descriptor.fragment.as_mut().unwrap().shader = shader;
+        descriptor.layout = Some(vec![
+            self.mesh_pipeline.view_layout.clone(),
+            self.mesh_pipeline.mesh_layout.clone(),
+        ]);
+        descriptor
+    }
+}
+
+type DrawInstanced = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+struct DrawMeshInstanced;
+
+impl EntityRenderCommand for DrawMeshInstanced {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<Handle<Mesh>>>,
+        SQuery<Read<InstanceBuffer>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, mesh_query, instance_buffer_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let mesh_handle = mesh_query.get(item).unwrap();
+        let instance_buffer = match instance_buffer_query.get(item) {
+            Ok(instance_buffer) => instance_buffer,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        // Meshes still loading are skipped for the frame
+        let gpu_mesh = match meshes.into_inner().get(mesh_handle) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+        let instances = 0..instance_buffer.length as u32;
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, instances);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, instances);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
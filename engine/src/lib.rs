@@ -1,14 +1,28 @@
 #![allow(clippy::type_complexity)]
 #![allow(warnings)]
 
+mod actions;
 mod camera_controller;
+mod camera_path;
+mod camera_views;
 mod editor;
+mod follow_camera;
+mod instancing;
 mod loaders;
+mod pan_zoom_camera;
 mod shapes;
+mod storage;
 
 use bevy::{diagnostic::*, prelude::*, window::{WindowMode, Windows}, asset::AssetServerSettings };
 use bevy_inspector_egui::{WorldInspectorParams, WorldInspectorPlugin};
+use actions::InputActionsPlugin;
 use camera_controller::CameraControllerPlugin;
+use camera_path::CameraPathPlugin;
+use camera_views::CameraViewsPlugin;
+use follow_camera::FollowCameraPlugin;
+use instancing::InstancingPlugin;
+use pan_zoom_camera::PanZoomCameraPlugin;
+use storage::StoragePlugin;
 
 #[cfg(not(target_arch = "wasm32"))]
 use bevy::app::AppExit;
@@ -22,7 +36,7 @@ use bevy::app::AppExit;
 
 pub mod prelude {
     pub use crate::{
-        camera_controller::*, editor::*, loaders::*, shapes::*,
+        actions::*, camera_controller::*, camera_path::*, camera_views::*, editor::*, follow_camera::*, instancing::*, loaders::*, pan_zoom_camera::*, shapes::*, storage::*,
         EnginePlugin,
     };
 }
@@ -64,7 +78,20 @@ impl Plugin for EnginePlugin {
         })
         .add_plugin(WorldInspectorPlugin::default())
         .add_plugin(editor::EditorPlugin)
+        .add_plugin(InputActionsPlugin {
+            path: format!("{}_input.ron", self.title.to_lowercase()).into(),
+        })
         .add_plugin(CameraControllerPlugin)
+        .add_plugin(CameraPathPlugin)
+        .add_plugin(CameraViewsPlugin {
+            path: format!("{}_cameras.ron", self.title.to_lowercase()).into(),
+        })
+        .add_plugin(FollowCameraPlugin)
+        .add_plugin(PanZoomCameraPlugin)
+        .add_plugin(InstancingPlugin)
+        .add_plugin(StoragePlugin {
+            path: format!("{}_storage.ron", self.title.to_lowercase()).into(),
+        })
         //.add_plugin(ShapePlugin)
         .add_plugin(FrameTimeDiagnosticsPlugin);
         //.add_plugin(LogDiagnosticsPlugin::default());
@@ -1,7 +1,26 @@
 use bevy::prelude::HandleUntyped;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::asset::{AssetServerSettings, FileAssetIo};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
 
 mod ron_asset;
+mod ron_dependencies;
+#[cfg(not(target_arch = "wasm32"))]
+mod ron_file;
+mod ron_migration;
 
 pub use ron_asset::*;
+pub use ron_dependencies::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use ron_file::*;
+pub use ron_migration::*;
 
 pub struct AssetsLoading(pub Vec<HandleUntyped>);
+
+/// Where an asset lives on disk, for tools that write assets back out
+#[cfg(not(target_arch = "wasm32"))]
+pub fn asset_file_path(settings: Option<&AssetServerSettings>, path: impl AsRef<Path>) -> PathBuf {
+    let folder = settings.map_or("assets", |s| s.asset_folder.as_str());
+    FileAssetIo::get_root_path().join(folder).join(path)
+}
@@ -5,12 +5,17 @@ use bevy::{
     reflect::TypeUuid,
     utils::BoxedFuture,
 };
+use anyhow::Context;
 use serde::Deserialize;
-use std::marker::PhantomData;
+
+use super::{
+    DependencyResolver, ResolveDependencies, RonDependencies, RonMigrations, DEPENDENCIES_LABEL,
+};
 
 struct RonLoader<T> {
     extensions: Vec<&'static str>,
-    _t: PhantomData<fn() -> T>,
+    migrations: RonMigrations,
+    resolve: Option<fn(&mut T, &mut DependencyResolver)>,
 }
 
 impl<T> AssetLoader for RonLoader<T>
@@ -27,8 +32,23 @@ where
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let loaded = ron::de::from_bytes::<T>(bytes)?;
-            load_context.set_default_asset(LoadedAsset::new(loaded));
+            let mut loaded = self
+                .migrations
+                .deserialize::<T>(bytes)
+                .with_context(|| format!("failed to load {}", load_context.path().display()))?;
+
+            let mut resolver = DependencyResolver::new(Some(&*load_context));
+            if let Some(resolve) = self.resolve {
+                resolve(&mut loaded, &mut resolver);
+            }
+            let (paths, handles) = resolver.finish();
+            if !handles.is_empty() {
+                load_context.set_labeled_asset(
+                    DEPENDENCIES_LABEL,
+                    LoadedAsset::new(RonDependencies(handles)),
+                );
+            }
+            load_context.set_default_asset(LoadedAsset::new(loaded).with_dependencies(paths));
             Ok(())
         })
     }
@@ -39,7 +59,8 @@ where
 /// Create and register as many instances as you need.
 pub struct RonAssetPlugin<T> {
     extensions: Vec<&'static str>,
-    _t: PhantomData<fn() -> T>,
+    migrations: RonMigrations,
+    resolve: Option<fn(&mut T, &mut DependencyResolver)>,
 }
 
 impl<T> Plugin for RonAssetPlugin<T>
@@ -49,8 +70,12 @@ where
     fn build(&self, app: &mut App) {
         let loader = RonLoader::<T> {
             extensions: self.extensions.clone(),
-            _t: PhantomData,
+            migrations: self.migrations.clone(),
+            resolve: self.resolve,
         };
+        if app.world.get_resource::<Assets<RonDependencies>>().is_none() {
+            app.add_asset::<RonDependencies>();
+        }
         app.add_asset::<T>().add_asset_loader(loader);
     }
 }
@@ -66,7 +91,25 @@ impl<T> RonAssetPlugin<T> {
     pub fn new(extensions: &[&'static str]) -> Self {
         Self {
             extensions: extensions.to_owned(),
-            _t: PhantomData,
+            migrations: RonMigrations::default(),
+            resolve: None,
         }
     }
+
+    /// Upgrade older files to the current schema before deserializing.
+    ///
+    /// See [`RonMigrations`] for the version header format.
+    pub fn with_migrations(mut self, migrations: RonMigrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+}
+
+impl<T: ResolveDependencies> RonAssetPlugin<T> {
+    /// Resolve the [`AssetRef`](super::AssetRef) fields of `T` once a file is deserialized, so
+    /// the referenced assets load with it and count towards its load state
+    pub fn with_dependencies(mut self) -> Self {
+        self.resolve = Some(T::resolve_dependencies);
+        self
+    }
 }
@@ -0,0 +1,224 @@
+// Lets RON assets reference other assets by path, resolved through the LoadContext once the
+// file is deserialized
+use bevy::{
+    asset::{Asset, AssetPath, HandleId, LoadContext, LoadState},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::AssetsLoading;
+
+/// Label of the [`RonDependencies`] sub asset of a RON file
+pub const DEPENDENCIES_LABEL: &str = "dependencies";
+
+/// Strong handles to everything a RON file referenced, stored as a labeled sub asset
+/// so dependencies stay loaded and can be found again for load state checks
+#[derive(TypeUuid)]
+#[uuid = "6b0b5f4c-2d2f-4a0c-9a52-3c1e5b8f7d21"]
+pub struct RonDependencies(pub Vec<HandleUntyped>);
+
+/// An asset path in a RON file, written as a plain string relative to the asset folder, the
+/// same as `AssetServer::load`.
+///
+/// The handle is weak until the loader resolves it, see [`ResolveDependencies`]:
+/// ```ignore
+/// #[derive(Deserialize, TypeUuid)]
+/// #[uuid = "..."]
+/// struct Skin {
+///     texture: AssetRef<Image>,
+/// }
+///
+/// impl ResolveDependencies for Skin {
+///     fn resolve_dependencies(&mut self, resolver: &mut DependencyResolver) {
+///         resolver.resolve(&mut self.texture);
+///     }
+/// }
+///
+/// app.add_plugin(RonAssetPlugin::<Skin>::new(&["skin.ron"]).with_dependencies());
+/// ```
+#[derive(Serialize, Deserialize)]
+#[serde(from = "String", into = "String", bound = "")]
+pub struct AssetRef<T: Asset> {
+    path: String,
+    handle: Handle<T>,
+}
+
+impl<T: Asset> AssetRef<T> {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn handle(&self) -> &Handle<T> {
+        &self.handle
+    }
+}
+
+impl<T: Asset> From<String> for AssetRef<T> {
+    fn from(path: String) -> Self {
+        let handle = Handle::weak(HandleId::from(AssetPath::from(path.as_str())));
+        Self { path, handle }
+    }
+}
+
+impl<T: Asset> From<AssetRef<T>> for String {
+    fn from(asset_ref: AssetRef<T>) -> Self {
+        asset_ref.path
+    }
+}
+
+impl<T: Asset> Clone for AssetRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            handle: self.handle.clone(),
+        }
+    }
+}
+
+impl<T: Asset> fmt::Debug for AssetRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AssetRef").field(&self.path).finish()
+    }
+}
+
+/// RON assets with [`AssetRef`] fields, registered with [`RonAssetPlugin::with_dependencies`]
+pub trait ResolveDependencies {
+    /// Pass every [`AssetRef`] to [`DependencyResolver::resolve`]
+    fn resolve_dependencies(&mut self, resolver: &mut DependencyResolver);
+}
+
+/// Swaps the weak handles of [`AssetRef`]s for strong ones and keeps track of every path, so the
+/// loader can declare them as dependencies
+pub struct DependencyResolver<'a, 'b> {
+    /// None outside the asset server, handles stay weak
+    load_context: Option<&'a LoadContext<'b>>,
+    paths: Vec<AssetPath<'static>>,
+    handles: Vec<HandleUntyped>,
+}
+
+impl<'a, 'b> DependencyResolver<'a, 'b> {
+    pub(crate) fn new(load_context: Option<&'a LoadContext<'b>>) -> Self {
+        Self {
+            load_context,
+            paths: Vec::new(),
+            handles: Vec::new(),
+        }
+    }
+
+    pub fn resolve<T: Asset>(&mut self, asset_ref: &mut AssetRef<T>) {
+        let path = AssetPath::from(asset_ref.path.as_str()).to_owned();
+        if let Some(load_context) = self.load_context {
+            asset_ref.handle = load_context.get_handle(path.clone());
+        }
+        self.handles.push(asset_ref.handle.clone_untyped());
+        self.paths.push(path);
+    }
+
+    /// Every resolved path and its handle, in the order they were resolved
+    pub(crate) fn finish(self) -> (Vec<AssetPath<'static>>, Vec<HandleUntyped>) {
+        (self.paths, self.handles)
+    }
+}
+
+impl AssetsLoading {
+    /// Group load state of every handle, including the dependencies of any RON files
+    pub fn load_state(
+        &self,
+        asset_server: &AssetServer,
+        dependencies: &Assets<RonDependencies>,
+    ) -> LoadState {
+        let mut ids = Vec::new();
+        let mut pending = self.0.iter().map(|h| h.id).collect::<Vec<_>>();
+        while let Some(id) = pending.pop() {
+            if ids.contains(&id) {
+                continue;
+            }
+            ids.push(id);
+
+            if let Some(path) = asset_server.get_handle_path(id) {
+                let deps_id =
+                    HandleId::from(AssetPath::new_ref(path.path(), Some(DEPENDENCIES_LABEL)));
+                if let Some(deps) = dependencies.get(deps_id) {
+                    pending.extend(deps.0.iter().map(|h| h.id));
+                }
+            }
+        }
+        asset_server.get_group_load_state(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct Skin {
+        texture: AssetRef<Image>,
+        #[serde(default)]
+        normal_map: Option<AssetRef<Image>>,
+        meshes: Vec<AssetRef<Mesh>>,
+    }
+
+    impl ResolveDependencies for Skin {
+        fn resolve_dependencies(&mut self, resolver: &mut DependencyResolver) {
+            resolver.resolve(&mut self.texture);
+            if let Some(normal_map) = &mut self.normal_map {
+                resolver.resolve(normal_map);
+            }
+            for mesh in &mut self.meshes {
+                resolver.resolve(mesh);
+            }
+        }
+    }
+
+    #[test]
+    fn asset_refs_are_paths() {
+        let skin = ron::de::from_str::<Skin>(
+            r#"(texture: "textures/skin.png", meshes: ["models/a.gltf#Mesh0/Primitive0"])"#,
+        )
+        .unwrap();
+        assert_eq!(skin.texture.path(), "textures/skin.png");
+        assert!(skin.normal_map.is_none());
+        assert_eq!(
+            skin.texture.handle().id,
+            HandleId::from(AssetPath::from("textures/skin.png"))
+        );
+
+        let text = ron::ser::to_string(&skin).unwrap();
+        assert!(text.contains(r#"texture:"textures/skin.png""#));
+    }
+
+    #[test]
+    fn every_asset_ref_is_a_dependency() {
+        let mut skin = ron::de::from_str::<Skin>(
+            r#"(
+                texture: "textures/skin.png",
+                normal_map: Some("textures/skin_normal.png"),
+                meshes: ["models/a.gltf#Mesh0/Primitive0"],
+            )"#,
+        )
+        .unwrap();
+        let mut resolver = DependencyResolver::new(None);
+        skin.resolve_dependencies(&mut resolver);
+        let (paths, handles) = resolver.finish();
+
+        let expected = [
+            AssetPath::from("textures/skin.png"),
+            AssetPath::from("textures/skin_normal.png"),
+            AssetPath::new_ref("models/a.gltf".as_ref(), Some("Mesh0/Primitive0")),
+        ]
+        .iter()
+        .map(AssetPath::get_id)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            paths.iter().map(AssetPath::get_id).collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(
+            handles.iter().map(|handle| handle.id).collect::<Vec<_>>(),
+            expected.into_iter().map(HandleId::from).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,35 @@
+// Plain RON files outside the asset server, for settings and files written by editor tools
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::Path};
+
+pub fn load_ron_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> anyhow::Result<T> {
+    let text = fs::read_to_string(path)?;
+    Ok(ron::de::from_str(&text)?)
+}
+
+/// [`load_ron_file`] falling back to the default, only warns when the file exists but doesn't
+/// parse
+pub fn load_ron_file_or_default<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> T {
+    let path = path.as_ref();
+    match load_ron_file(path) {
+        Ok(value) => value,
+        Err(err) => {
+            if path.exists() {
+                warn!("failed to load {}: {}", path.display(), err);
+            }
+            T::default()
+        }
+    }
+}
+
+/// Pretty printed, creating the parent folder if needed
+pub fn save_ron_file<T: Serialize>(path: impl AsRef<Path>, value: &T) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new())?;
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
@@ -0,0 +1,324 @@
+// Versioned RON files, lets asset types change shape without breaking old files
+use anyhow::{anyhow, bail, Context};
+use ron::{ser::PrettyConfig, Value};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Upgrades a RON value from one version to the next
+pub type RonMigration = fn(Value) -> anyhow::Result<Value>;
+
+const VERSION_HEADER: &str = "#![version(";
+
+/// Migrations for a single RON asset type.
+///
+/// Files may start with a `#![version(N)]` header line, files without one are treated as
+/// version 0.  Older files are parsed into a [`ron::Value`], run through each registered
+/// migration in order, and then deserialized as the current version.
+///
+/// Note: [`ron::Value`] drops struct names and can't represent enum variants with data, so
+/// migrations work best on plain structs, maps and lists.
+#[derive(Clone, Default)]
+pub struct RonMigrations {
+    version: u32,
+    steps: HashMap<u32, RonMigration>,
+}
+
+impl RonMigrations {
+    /// Create migrations where `version` is the current schema version
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            steps: HashMap::default(),
+        }
+    }
+
+    /// Register a migration upgrading a file from version `from` to `from + 1`
+    pub fn add(mut self, from: u32, migration: RonMigration) -> Self {
+        assert!(
+            from < self.version,
+            "migration from version {} is not older than current version {}",
+            from,
+            self.version
+        );
+        self.steps.insert(from, migration);
+        self
+    }
+
+    /// The current schema version
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Run every migration needed to bring `value` from version `from` to current
+    pub fn migrate(&self, from: u32, mut value: Value) -> anyhow::Result<Value> {
+        for version in from..self.version {
+            let migration = self
+                .steps
+                .get(&version)
+                .ok_or_else(|| anyhow!("no migration registered from version {}", version))?;
+            value = migration(value)
+                .with_context(|| format!("migration from version {} failed", version))?;
+        }
+        Ok(value)
+    }
+
+    /// Deserialize a RON file of any known version as the current version of `T`
+    pub fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        let text = std::str::from_utf8(bytes)?;
+        let (version, body) = split_version_header(text)?;
+        let version = version.unwrap_or(0);
+
+        if version > self.version {
+            bail!(
+                "file is version {}, newer than the supported version {}",
+                version,
+                self.version
+            );
+        }
+
+        if version == self.version {
+            return Ok(ron::de::from_str::<T>(body)?);
+        }
+
+        let value = ron::de::from_str::<Value>(body)
+            .with_context(|| format!("failed to parse version {} file", version))?;
+        let value = self.migrate(version, value)?;
+        value
+            .into_rust::<T>()
+            .with_context(|| format!("failed to deserialize migrated version {} file", version))
+    }
+
+    /// Serialize `value` as the current version, including the version header
+    pub fn serialize<T: Serialize>(&self, value: &T) -> anyhow::Result<String> {
+        let body = ron::ser::to_string_pretty(value, PrettyConfig::new())?;
+        Ok(format!("{}{})]\n{}\n", VERSION_HEADER, self.version, body))
+    }
+
+    /// Rewrite a file in place as the current version, returns false if it was already current
+    pub fn migrate_file<T>(&self, path: impl AsRef<Path>) -> anyhow::Result<bool>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        // No header is version 0, so files are left alone when that's still current
+        if split_version_header(&text)?.0.unwrap_or(0) == self.version {
+            return Ok(false);
+        }
+
+        let value = self.deserialize::<T>(text.as_bytes())?;
+        fs::write(path, self.serialize(&value)?)?;
+        Ok(true)
+    }
+}
+
+/// Split the optional `#![version(N)]` header from the rest of a RON file
+pub fn split_version_header(text: &str) -> anyhow::Result<(Option<u32>, &str)> {
+    let trimmed = text.trim_start();
+    if !trimmed.starts_with(VERSION_HEADER) {
+        return Ok((None, text));
+    }
+
+    let end = trimmed
+        .find(")]")
+        .ok_or_else(|| anyhow!("unterminated version header"))?;
+    let version = trimmed[VERSION_HEADER.len()..end]
+        .trim()
+        .parse::<u32>()
+        .context("invalid version header")?;
+    Ok((Some(version), &trimmed[end + 2..]))
+}
+
+/// Command line entry point to upgrade RON files in place to the latest version.
+///
+/// Usage: `<bin> [--check] <files>...`, with `--check` nothing is written and an error is
+/// returned if any file is out of date.
+pub fn run_migrate_cli<T>(migrations: &RonMigrations) -> anyhow::Result<()>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        bail!("usage: [--check] <files>...");
+    }
+
+    let mut outdated = 0;
+    for path in paths.iter() {
+        if check {
+            let text = fs::read_to_string(path)?;
+            let version = split_version_header(&text)?.0.unwrap_or(0);
+            if version != migrations.version() {
+                println!("{}: version {}", path, version);
+                outdated += 1;
+            }
+        } else if migrations
+            .migrate_file::<T>(path)
+            .with_context(|| format!("failed to migrate {}", path))?
+        {
+            println!("{}: migrated to version {}", path, migrations.version());
+        }
+    }
+
+    if outdated > 0 {
+        bail!("{} file(s) need migrating", outdated);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Settings {
+        walk_speed: f32,
+        run_speed: f32,
+    }
+
+    fn field(name: &str) -> Value {
+        Value::String(name.to_string())
+    }
+
+    fn rename_speed(value: Value) -> anyhow::Result<Value> {
+        let mut map = match value {
+            Value::Map(map) => map,
+            _ => bail!("expected a struct"),
+        };
+        let speed = map
+            .remove(&field("speed"))
+            .ok_or_else(|| anyhow!("missing speed"))?;
+        map.insert(field("walk_speed"), speed);
+        Ok(Value::Map(map))
+    }
+
+    fn add_run_speed(value: Value) -> anyhow::Result<Value> {
+        let mut map = match value {
+            Value::Map(map) => map,
+            _ => bail!("expected a struct"),
+        };
+        let walk_speed = map[&field("walk_speed")].clone().into_rust::<f32>()?;
+        map.insert(
+            field("run_speed"),
+            Value::Number(ron::Number::new(walk_speed as f64 * 3.0)),
+        );
+        Ok(Value::Map(map))
+    }
+
+    fn migrations() -> RonMigrations {
+        RonMigrations::new(2)
+            .add(0, rename_speed)
+            .add(1, add_run_speed)
+    }
+
+    #[test]
+    fn split_version_header_reads_the_version() {
+        let (version, body) = split_version_header("#![version(3)]\n(a: 1)").unwrap();
+        assert_eq!(version, Some(3));
+        assert_eq!(body.trim(), "(a: 1)");
+
+        let (version, body) = split_version_header("  #![version( 12 )](a: 1)").unwrap();
+        assert_eq!(version, Some(12));
+        assert_eq!(body, "(a: 1)");
+    }
+
+    #[test]
+    fn split_version_header_without_header() {
+        let text = "(a: 1)";
+        assert_eq!(split_version_header(text).unwrap(), (None, text));
+    }
+
+    #[test]
+    fn split_version_header_rejects_bad_headers() {
+        assert!(split_version_header("#![version(2)").is_err());
+        assert!(split_version_header("#![version(two)]\n()").is_err());
+    }
+
+    #[test]
+    fn migrations_run_in_order() {
+        let settings = migrations()
+            .deserialize::<Settings>(b"(speed: 2.0)")
+            .unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                walk_speed: 2.0,
+                run_speed: 6.0,
+            }
+        );
+
+        let settings = migrations()
+            .deserialize::<Settings>(b"#![version(1)]\n(walk_speed: 1.0)")
+            .unwrap();
+        assert_eq!(settings.run_speed, 3.0);
+    }
+
+    #[test]
+    fn current_version_skips_migrations() {
+        let settings = migrations()
+            .deserialize::<Settings>(b"#![version(2)]\n(walk_speed: 1.0, run_speed: 1.5)")
+            .unwrap();
+        assert_eq!(settings.run_speed, 1.5);
+    }
+
+    #[test]
+    fn newer_and_unknown_versions_fail() {
+        assert!(migrations()
+            .deserialize::<Settings>(b"#![version(3)]\n(walk_speed: 1.0, run_speed: 1.5)")
+            .is_err());
+        let missing_step = RonMigrations::new(2).add(1, add_run_speed);
+        assert!(missing_step
+            .deserialize::<Settings>(b"(speed: 2.0)")
+            .is_err());
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        let settings = Settings {
+            walk_speed: 1.0,
+            run_speed: 4.0,
+        };
+        let text = migrations().serialize(&settings).unwrap();
+        assert_eq!(split_version_header(&text).unwrap().0, Some(2));
+        assert_eq!(
+            migrations()
+                .deserialize::<Settings>(text.as_bytes())
+                .unwrap(),
+            settings
+        );
+    }
+
+    #[test]
+    fn migrate_file_leaves_current_files_alone() {
+        let path = std::env::temp_dir().join(format!("ron_migration_{}.ron", std::process::id()));
+
+        fs::write(&path, "(walk_speed: 1.0, run_speed: 2.0)").unwrap();
+        let unversioned = RonMigrations::new(0);
+        assert!(!unversioned.migrate_file::<Settings>(&path).unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "(walk_speed: 1.0, run_speed: 2.0)"
+        );
+
+        fs::write(&path, "(speed: 2.0)").unwrap();
+        assert!(migrations().migrate_file::<Settings>(&path).unwrap());
+        assert!(!migrations().migrate_file::<Settings>(&path).unwrap());
+        assert_eq!(
+            migrations()
+                .deserialize::<Settings>(fs::read_to_string(&path).unwrap().as_bytes())
+                .unwrap()
+                .run_speed,
+            6.0
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
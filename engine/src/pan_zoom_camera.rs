@@ -0,0 +1,147 @@
+use bevy::{core::Time, prelude::*, window::Windows};
+
+use crate::{actions::*, camera_controller::*, follow_camera::FollowCamera};
+
+pub const CAMERA_DRAG: &str = "camera_drag";
+pub const CAMERA_PAN_X: &str = "camera_pan_x";
+pub const CAMERA_PAN_Y: &str = "camera_pan_y";
+
+pub struct PanZoomCameraPlugin;
+
+impl Plugin for PanZoomCameraPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        use InputBinding::*;
+
+        app.add_input_action(CAMERA_DRAG, &[Mouse(MouseButton::Right)])
+            .add_input_axis(
+                CAMERA_PAN_X,
+                &[AxisBinding::Buttons {
+                    negative: Key(KeyCode::Left),
+                    positive: Key(KeyCode::Right),
+                }],
+            )
+            .add_input_axis(
+                CAMERA_PAN_Y,
+                &[AxisBinding::Buttons {
+                    negative: Key(KeyCode::Down),
+                    positive: Key(KeyCode::Up),
+                }],
+            )
+            .add_system(update_pan_zoom_camera);
+    }
+}
+
+/// 2D controller for orthographic cameras, camera_drag or the camera_pan axes to pan,
+/// camera_zoom to zoom around the cursor. Panning pauses while a [`FollowCamera`] on the same
+/// camera has a target
+#[derive(Component)]
+pub struct PanZoomCamera {
+    pub enabled: bool,
+    /// Use the camera_pan axes, turn off when a game needs the same keys
+    pub keyboard_pan: bool,
+    /// Keyboard pan speed in screen heights per second
+    pub pan_speed: f32,
+    pub zoom_sensitivity: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// World space min and max corners the view is kept inside
+    pub bounds: Option<(Vec2, Vec2)>,
+}
+
+impl Default for PanZoomCamera {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keyboard_pan: true,
+            pan_speed: 1.0,
+            zoom_sensitivity: 0.1,
+            min_scale: 0.1,
+            max_scale: 1000.0,
+            bounds: None,
+        }
+    }
+}
+
+fn update_pan_zoom_camera(
+    time: Res<Time>,
+    actions: Res<InputActions>,
+    windows: Res<Windows>,
+    mut query: Query<(
+        &mut Transform,
+        &mut OrthographicProjection,
+        &PanZoomCamera,
+        Option<&FollowCamera>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    let window = windows.get_primary().unwrap();
+    let window_size = Vec2::new(window.width(), window.height());
+
+    let mouse_motion = Vec2::new(actions.axis(CAMERA_LOOK_X), actions.axis(CAMERA_LOOK_Y));
+    let scroll = actions.axis(CAMERA_ZOOM);
+
+    for (mut transform, mut projection, options, follow) in query.iter_mut() {
+        if !options.enabled {
+            continue;
+        }
+        let following = follow.map_or(false, |follow| follow.enabled && follow.target.is_some());
+
+        // Size of the view in world units at scale 1.0
+        let view_size = Vec2::new(
+            projection.right - projection.left,
+            projection.top - projection.bottom,
+        );
+        let world_per_pixel = view_size * projection.scale / window_size;
+
+        // Drag pan, motion y is down
+        if actions.pressed(CAMERA_DRAG) && !following {
+            transform.translation.x -= mouse_motion.x * world_per_pixel.x;
+            transform.translation.y += mouse_motion.y * world_per_pixel.y;
+        }
+
+        // Keyboard pan
+        if options.keyboard_pan && !following {
+            let axis_input = Vec2::new(actions.axis(CAMERA_PAN_X), actions.axis(CAMERA_PAN_Y))
+                .clamp_length_max(1.0);
+            if axis_input != Vec2::ZERO {
+                let offset = axis_input * options.pan_speed * view_size.y * projection.scale * dt;
+                transform.translation += offset.extend(0.0);
+            }
+        }
+
+        // Zoom, keeping the world point under the cursor fixed
+        if scroll != 0.0 {
+            let old_scale = projection.scale;
+            let new_scale = (old_scale * (1.0 - scroll * options.zoom_sensitivity))
+                .clamp(options.min_scale, options.max_scale);
+
+            if let Some(cursor) = window.cursor_position() {
+                let uv = cursor / window_size;
+                let cursor_offset = Vec2::new(
+                    projection.left + uv.x * view_size.x,
+                    projection.bottom + uv.y * view_size.y,
+                );
+                let offset = cursor_offset * (old_scale - new_scale);
+                transform.translation += offset.extend(0.0);
+            }
+            projection.scale = new_scale;
+        }
+
+        // Keep the view inside the bounds, centering if the view is larger
+        if let Some((min, max)) = options.bounds {
+            let half_view = view_size * projection.scale * 0.5;
+            let center = (min + max) * 0.5;
+            let pos = &mut transform.translation;
+            pos.x = if half_view.x * 2.0 >= max.x - min.x {
+                center.x
+            } else {
+                pos.x.clamp(min.x + half_view.x, max.x - half_view.x)
+            };
+            pos.y = if half_view.y * 2.0 >= max.y - min.y {
+                center.y
+            } else {
+                pos.y.clamp(min.y + half_view.y, max.y - half_view.y)
+            };
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::loaders::{load_ron_file_or_default, save_ron_file};
+
+/// Small key value store for things that should survive a restart, like high scores. Native
+/// keeps every key in one RON file, wasm uses the browser's local storage
+pub struct StoragePlugin {
+    /// RON file values are loaded from and saved to on native, on wasm the file name is used as
+    /// the local storage key prefix
+    pub path: PathBuf,
+}
+
+impl Plugin for StoragePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Storage::load(self.path.clone()));
+    }
+}
+
+pub struct Storage {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl Storage {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Stores and saves right away
+    pub fn set(&mut self, key: &str, value: impl Into<String>) -> anyhow::Result<()> {
+        self.values.insert(key.to_string(), value.into());
+        self.save(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> anyhow::Result<()> {
+        self.values.remove(key);
+        self.save(key)
+    }
+
+    /// `None` when missing, or when it no longer parses as `T`
+    pub fn get_ron<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let text = self.get(key)?;
+        match ron::de::from_str(text) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                warn!("failed to parse stored {}: {}", key, err);
+                None
+            }
+        }
+    }
+
+    pub fn set_ron<T: Serialize>(&mut self, key: &str, value: &T) -> anyhow::Result<()> {
+        self.set(key, ron::ser::to_string(value)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load(path: PathBuf) -> Self {
+        let values = load_ron_file_or_default(&path);
+        Self { path, values }
+    }
+
+    // Every key is rewritten, the file only holds a few small values
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&self, _key: &str) -> anyhow::Result<()> {
+        save_ron_file(&self.path, &self.values)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn prefix(path: &std::path::Path) -> String {
+        format!("{}:", path.display())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load(path: PathBuf) -> Self {
+        let prefix = Self::prefix(&path);
+        let mut values = BTreeMap::new();
+        if let Some(storage) = Self::local_storage() {
+            let length = storage.length().unwrap_or(0);
+            for i in 0..length {
+                let key = match storage.key(i) {
+                    Ok(Some(key)) => key,
+                    _ => continue,
+                };
+                if let Some(name) = key.strip_prefix(&prefix) {
+                    if let Ok(Some(value)) = storage.get_item(&key) {
+                        values.insert(name.to_string(), value);
+                    }
+                }
+            }
+        } else {
+            warn!("local storage unavailable, values won't be saved");
+        }
+        Self { path, values }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save(&self, key: &str) -> anyhow::Result<()> {
+        let storage =
+            Self::local_storage().ok_or_else(|| anyhow::anyhow!("local storage unavailable"))?;
+        let item = format!("{}{}", Self::prefix(&self.path), key);
+        let result = match self.values.get(key) {
+            Some(value) => storage.set_item(&item, value),
+            None => storage.remove_item(&item),
+        };
+        result.map_err(|err| anyhow::anyhow!("failed to save {}: {:?}", key, err))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Score {
+        length: usize,
+        time_alive: f32,
+    }
+
+    #[test]
+    fn ron_values_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("storage_{}.ron", std::process::id()));
+        let scores = vec![
+            Score {
+                length: 12,
+                time_alive: 3.5,
+            },
+            Score {
+                length: 4,
+                time_alive: 1.25,
+            },
+        ];
+
+        let mut storage = Storage::load(path.clone());
+        storage.set_ron("scores", &scores).unwrap();
+        storage.set("name", "snake").unwrap();
+        assert_eq!(storage.get_ron::<Vec<Score>>("scores"), Some(scores));
+
+        let reloaded = Storage::load(path.clone());
+        assert_eq!(
+            reloaded.get_ron::<Vec<Score>>("scores"),
+            storage.get_ron::<Vec<Score>>("scores")
+        );
+        assert_eq!(reloaded.get("name"), Some("snake"));
+        assert_eq!(reloaded.get_ron::<Vec<Score>>("name"), None);
+        assert_eq!(reloaded.get_ron::<Vec<Score>>("missing"), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}